@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: Joonas Javanainen <joonas@merulogic.com>
+//
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implementation shared by the blocking and asynchronous drivers
+use core::fmt;
+use embedded_hal::i2c::{Error, ErrorKind};
+
+use crate::{
+    regs::{self, Register8, RegisterAddress},
+    Ak09916Error, Calibration, Measurement, Mode, WhoIAm, I2C_ADDRESS,
+};
+
+/// Driver state shared by the blocking and asynchronous drivers
+#[derive(Clone)]
+pub(crate) struct Ak09916Inner<I, D> {
+    pub(crate) i2c: I,
+    pub(crate) delay: D,
+    pub(crate) calibration: Option<Calibration>,
+    /// Last mode written to the device, or None if unknown
+    pub(crate) current_mode: Option<Mode>,
+    /// Last fresh measurement returned by `read_measurement_or_stale`
+    pub(crate) last_measurement: Option<Measurement>,
+    /// Callback for measurements that have the overflow flag set
+    pub(crate) overflow_callback: Option<fn(&Measurement)>,
+    /// Poll interval set by the user, if any
+    pub(crate) poll_interval_us: Option<u32>,
+}
+
+impl<I, D> Ak09916Inner<I, D> {
+    pub(crate) fn new(i2c: I, delay: D) -> Self {
+        Ak09916Inner::from_parts(i2c, delay, None)
+    }
+    pub(crate) fn from_parts(i2c: I, delay: D, calibration: Option<Calibration>) -> Self {
+        Ak09916Inner {
+            i2c,
+            delay,
+            calibration,
+            current_mode: None,
+            last_measurement: None,
+            overflow_callback: None,
+            poll_interval_us: None,
+        }
+    }
+    pub(crate) fn release(self) -> (I, D) {
+        let (i2c, delay, _) = self.into_parts();
+        (i2c, delay)
+    }
+    pub(crate) fn into_parts(self) -> (I, D, Option<Calibration>) {
+        let Ak09916Inner {
+            i2c,
+            delay,
+            calibration,
+            ..
+        } = self;
+        (i2c, delay, calibration)
+    }
+    /// Caches the mode of the device.
+    ///
+    /// Single measurement and self-test modes switch to power-down mode automatically, so they
+    /// are never cached.
+    pub(crate) fn set_mode(&mut self, mode: Option<Mode>) {
+        self.current_mode =
+            mode.filter(|mode| !matches!(mode, Mode::SingleMeasurement | Mode::SelfTest));
+    }
+    /// Returns the poll interval set by the user, or the recommended interval for the current mode
+    pub(crate) fn poll_interval_us(&self) -> u32 {
+        self.poll_interval_us
+            .unwrap_or_else(|| poll_interval_for_mode(self.current_mode.unwrap_or(Mode::PowerDown)))
+    }
+    /// Calls the overflow callback if the measurement has the overflow flag set
+    pub(crate) fn check_overflow(&self, measurement: Measurement) -> Measurement {
+        if let Some(callback) = self.overflow_callback {
+            if measurement.overflow() {
+                callback(&measurement);
+            }
+        }
+        measurement
+    }
+    /// Invalidates the cached mode before writing a register that can change the mode
+    pub(crate) fn before_write(&mut self, buffer: [u8; 2]) {
+        if let Ok(RegisterAddress::Cntl2 | RegisterAddress::Cntl3) =
+            RegisterAddress::try_from(buffer[0])
+        {
+            self.current_mode = None;
+        }
+    }
+    /// Caches the mode after a successful write to the mode register
+    pub(crate) fn after_write(&mut self, buffer: [u8; 2]) {
+        if buffer[0] == u8::from(RegisterAddress::Cntl2) {
+            self.set_mode(regs::Cntl2::from(buffer[1]).mode());
+        }
+    }
+}
+
+/// Formats the driver state without the I²C bus and delay, which don't necessarily implement
+/// `Debug`
+impl<I, D> fmt::Debug for Ak09916Inner<I, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ak09916")
+            .field("address", &format_args!("{:#04x}", I2C_ADDRESS))
+            .field("calibration", &self.calibration)
+            .field("current_mode", &self.current_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns true if the error means the device didn't acknowledge
+#[inline]
+pub(crate) fn is_nack<E: Error>(err: &E) -> bool {
+    matches!(err.kind(), ErrorKind::NoAcknowledge(_))
+}
+
+#[inline]
+pub(crate) fn who_i_am_from_raw_data(buffer: [u8; 2]) -> WhoIAm {
+    WhoIAm {
+        company_id: regs::Wia1::from(buffer[0]).0,
+        device_id: regs::Wia2::from(buffer[1]).0,
+    }
+}
+
+#[inline]
+pub(crate) fn register8_write_buffer<R: Register8>(register: R) -> [u8; 2] {
+    [u8::from(R::ADDRESS), register.into()]
+}
+
+/// Compares the mode register value that was read back to the mode that was written
+#[inline]
+pub(crate) fn verify_mode<E>(target_mode: Mode, cntl2: regs::Cntl2) -> Result<(), Ak09916Error<E>> {
+    if cntl2.0 == regs::ModeRegister::Mode(target_mode) {
+        Ok(())
+    } else {
+        Err(Ak09916Error::ModeSwitchFailed {
+            written: target_mode,
+            read_back: cntl2.0,
+        })
+    }
+}
+
+/// Returns the next poll delay within a time budget, or None if the budget is exhausted
+#[inline]
+pub(crate) fn next_poll_delay(
+    elapsed_us: u32,
+    deadline_us: u32,
+    poll_interval_us: u32,
+) -> Option<u32> {
+    if elapsed_us >= deadline_us {
+        None
+    } else {
+        Some(poll_interval_us.clamp(1, deadline_us - elapsed_us))
+    }
+}
+
+/// Returns a poll interval that is a tenth of the measurement period, or 1 ms if the mode doesn't
+/// have a measurement period
+#[inline]
+pub(crate) fn poll_interval_for_mode(mode: Mode) -> u32 {
+    match mode.sample_period_us() {
+        Some(period_us) => period_us / 10,
+        None => 1_000,
+    }
+}
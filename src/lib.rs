@@ -63,9 +63,14 @@ use bitflags::bitflags as bitflags_macro;
 #[cfg(feature = "defmt-1")]
 use crate::defmt::bitflags as bitflags_macro;
 
+mod inner;
 pub mod regs;
 
-use num_enum::{IntoPrimitive, TryFromPrimitive};
+use core::{
+    fmt,
+    ops::{Div, Index, IndexMut, Mul},
+};
+use num_enum::IntoPrimitive;
 
 /// I²C address of AK09916
 pub const I2C_ADDRESS: u8 = 0x0c;
@@ -75,13 +80,62 @@ pub const I2C_ADDRESS: u8 = 0x0c;
 /// use this, so this is only needed if you do mode switches with low-level functions like
 /// [`write_register8`](blocking::Ak09916::write_register8).
 pub const MODE_SET_WAIT_TIME_US: u32 = 100;
+/// Power-on reset time in μs
+///
+/// The device doesn't respond to I²C communication until this time has passed after power-up.
+/// [`wait_for_device`](blocking::Ak09916::wait_for_device) waits this long before the first
+/// attempt.
+pub const MODE_POWER_ON_RESET_WAIT_TIME_US: u32 = 100;
 /// Sensitivity of the sensor as nT / bit.
 ///
 /// This can be used to convert the raw measurement `hx` / `hy` / `hz` values to nanoteslas (nT).
 pub const SENSITIVITY_NT_PER_BIT: i32 = 150;
+/// Minimum valid X-axis self-test value (raw value)
+pub const SELF_TEST_MIN_X: i16 = -200;
+/// Maximum valid X-axis self-test value (raw value)
+pub const SELF_TEST_MAX_X: i16 = 200;
+/// Minimum valid Y-axis self-test value (raw value)
+pub const SELF_TEST_MIN_Y: i16 = -200;
+/// Maximum valid Y-axis self-test value (raw value)
+pub const SELF_TEST_MAX_Y: i16 = 200;
+/// Minimum valid Z-axis self-test value (raw value)
+pub const SELF_TEST_MIN_Z: i16 = -1000;
+/// Maximum valid Z-axis self-test value (raw value)
+pub const SELF_TEST_MAX_Z: i16 = -200;
+/// Datasheet X-axis self-test tolerance from the center of the valid range (raw value)
+pub const SELF_TEST_TOLERANCE_X: i16 = (SELF_TEST_MAX_X - SELF_TEST_MIN_X) / 2;
+/// Datasheet Y-axis self-test tolerance from the center of the valid range (raw value)
+pub const SELF_TEST_TOLERANCE_Y: i16 = (SELF_TEST_MAX_Y - SELF_TEST_MIN_Y) / 2;
+/// Datasheet Z-axis self-test tolerance from the center of the valid range (raw value)
+pub const SELF_TEST_TOLERANCE_Z: i16 = (SELF_TEST_MAX_Z - SELF_TEST_MIN_Z) / 2;
+/// Full-scale measurement range of the sensor as ± nT
+pub const MEASUREMENT_RANGE_NT: i32 = 4_912_000;
+
+/// Measurement range in nanoteslas (nT)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct MeasurementRange {
+    /// Minimum value (inclusive)
+    pub min_nt: i32,
+    /// Maximum value (inclusive)
+    pub max_nt: i32,
+}
+
+impl MeasurementRange {
+    /// Full-scale measurement range of AK09916
+    pub const AK09916: MeasurementRange = MeasurementRange {
+        min_nt: -MEASUREMENT_RANGE_NT,
+        max_nt: MEASUREMENT_RANGE_NT,
+    };
+
+    /// Returns true if the value is within the range
+    pub fn contains(&self, value_nt: i32) -> bool {
+        (self.min_nt..=self.max_nt).contains(&value_nt)
+    }
+}
 
 /// Who I Am register data
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub struct WhoIAm {
     /// Company ID
@@ -100,7 +154,7 @@ impl WhoIAm {
 
 /// Operation mode setting
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, IntoPrimitive)]
 #[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub enum Mode {
     /// Power-down mode
@@ -119,20 +173,361 @@ pub enum Mode {
     SelfTest = 0b10000,
 }
 
+/// Aliases matching the continuous measurement mode numbers in the datasheet.
+///
+/// The datasheet numbers the continuous measurement modes 1 to 4, so mode 4 is 100 Hz and there's
+/// no mode 5.
 impl Mode {
+    /// Alias for [`Mode::Continuous10Hz`] (continuous measurement mode 1)
+    pub const CONTINUOUS_MODE_1: Mode = Mode::Continuous10Hz;
+    /// Alias for [`Mode::Continuous20Hz`] (continuous measurement mode 2)
+    pub const CONTINUOUS_MODE_2: Mode = Mode::Continuous20Hz;
+    /// Alias for [`Mode::Continuous50Hz`] (continuous measurement mode 3)
+    pub const CONTINUOUS_MODE_3: Mode = Mode::Continuous50Hz;
+    /// Alias for [`Mode::Continuous100Hz`] (continuous measurement mode 4)
+    pub const CONTINUOUS_MODE_4: Mode = Mode::Continuous100Hz;
     /// Alias for [`Mode::Continuous10Hz`]
+    #[deprecated(note = "use `Mode::CONTINUOUS_MODE_1` or `Mode::Continuous10Hz` instead")]
     pub const CONTINUOUS_1: Mode = Mode::Continuous10Hz;
     /// Alias for [`Mode::Continuous20Hz`]
+    #[deprecated(note = "use `Mode::CONTINUOUS_MODE_2` or `Mode::Continuous20Hz` instead")]
     pub const CONTINUOUS_2: Mode = Mode::Continuous20Hz;
     /// Alias for [`Mode::Continuous50Hz`]
+    #[deprecated(note = "use `Mode::CONTINUOUS_MODE_3` or `Mode::Continuous50Hz` instead")]
     pub const CONTINUOUS_3: Mode = Mode::Continuous50Hz;
     /// Alias for [`Mode::Continuous100Hz`]
+    #[deprecated(note = "use `Mode::CONTINUOUS_MODE_4` or `Mode::Continuous100Hz` instead")]
     pub const CONTINUOUS_4: Mode = Mode::Continuous100Hz;
 }
 
-/// Measurement data
+impl Mode {
+    /// Returns all continuous measurement modes, from the slowest to the fastest
+    pub const fn continuous_variants() -> &'static [Mode; 4] {
+        &[
+            Mode::Continuous10Hz,
+            Mode::Continuous20Hz,
+            Mode::Continuous50Hz,
+            Mode::Continuous100Hz,
+        ]
+    }
+    /// Returns all modes in register value order
+    pub const fn all_variants() -> &'static [Mode; 7] {
+        &[
+            Mode::PowerDown,
+            Mode::SingleMeasurement,
+            Mode::Continuous10Hz,
+            Mode::Continuous20Hz,
+            Mode::Continuous50Hz,
+            Mode::Continuous100Hz,
+            Mode::SelfTest,
+        ]
+    }
+    /// Returns the measurement period in μs for continuous measurement modes, or None for other
+    /// modes
+    pub const fn sample_period_us(self) -> Option<u32> {
+        match self {
+            Mode::Continuous10Hz => Some(100_000),
+            Mode::Continuous20Hz => Some(50_000),
+            Mode::Continuous50Hz => Some(20_000),
+            Mode::Continuous100Hz => Some(10_000),
+            Mode::PowerDown | Mode::SingleMeasurement | Mode::SelfTest => None,
+        }
+    }
+}
+
+impl TryFrom<u8> for Mode {
+    type Error = InvalidMode;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00000 => Ok(Mode::PowerDown),
+            0b00001 => Ok(Mode::SingleMeasurement),
+            0b00010 => Ok(Mode::Continuous10Hz),
+            0b00100 => Ok(Mode::Continuous20Hz),
+            0b00110 => Ok(Mode::Continuous50Hz),
+            0b01000 => Ok(Mode::Continuous100Hz),
+            0b10000 => Ok(Mode::SelfTest),
+            _ => Err(InvalidMode { value }),
+        }
+    }
+}
+
+/// Error returned when converting an invalid value to [`Mode`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct InvalidMode {
+    /// Value that doesn't match any mode
+    pub value: u8,
+}
+
+impl fmt::Display for InvalidMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid mode: {:#04x}", self.value)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for InvalidMode {}
+
+/// Driver error
+///
+/// New variants may be added in minor releases.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Ak09916Error<E> {
+    /// I²C bus error
+    I2c(E),
+    /// Mode register doesn't contain the mode that was written
+    ModeSwitchFailed {
+        /// Mode that was written
+        written: Mode,
+        /// Mode register value that was read back
+        read_back: regs::ModeRegister,
+    },
+    /// Measurement data was not available within the configured limits
+    Timeout,
+    /// Device didn't respond within the configured number of retries
+    DeviceNotFound,
+    /// Register dump failed validation
+    InvalidRegisterDump(regs::RegisterDumpError),
+    /// Device IDs don't match AK09916
+    WrongDevice(WhoIAm),
+    /// Heading is undefined because the X and Y values are both zero
+    ZeroField,
+}
+
+impl<E: fmt::Debug> fmt::Display for Ak09916Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ak09916Error::I2c(err) => write!(f, "I²C error: {:?}", err),
+            Ak09916Error::ModeSwitchFailed { written, read_back } => write!(
+                f,
+                "mode switch failed: wrote {:?}, read back {}",
+                written, read_back
+            ),
+            Ak09916Error::Timeout => f.write_str("measurement timed out"),
+            Ak09916Error::DeviceNotFound => f.write_str("device not found"),
+            Ak09916Error::InvalidRegisterDump(err) => {
+                write!(f, "invalid register dump: {:?}", err)
+            }
+            Ak09916Error::WrongDevice(who_i_am) => write!(
+                f,
+                "wrong device: company ID {:#04x}, device ID {:#04x}",
+                who_i_am.company_id, who_i_am.device_id
+            ),
+            Ak09916Error::ZeroField => f.write_str("heading is undefined for a zero field"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl<E: core::error::Error + 'static> core::error::Error for Ak09916Error<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Ak09916Error::I2c(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Measurement axis
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub enum Axis {
+    /// X-axis
+    X,
+    /// Y-axis
+    Y,
+    /// Z-axis
+    Z,
+}
+
+impl Axis {
+    /// Returns all axes in order
+    pub const fn all() -> [Axis; 3] {
+        [Axis::X, Axis::Y, Axis::Z]
+    }
+}
+
+impl From<Axis> for usize {
+    fn from(value: Axis) -> Self {
+        match value {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+impl fmt::Display for Axis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        })
+    }
+}
+
+/// Coordinate frame of measurement axes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub enum CoordinateFrame {
+    /// Axes of the sensor itself.
+    ///
+    /// The sensor axes form a right-handed frame with Z pointing down, so this is the same as
+    /// [`CoordinateFrame::Ned`] when the sensor X-axis points north.
+    SensorDefault,
+    /// North-East-Down
+    Ned,
+    /// East-North-Up
+    Enu,
+    /// North-West-Up
+    Nwu,
+}
+
+impl CoordinateFrame {
+    /// Converts axis values in this frame to North-East-Down
+    fn frame_to_ned(self, [a, b, c]: [i16; 3]) -> [i16; 3] {
+        match self {
+            CoordinateFrame::SensorDefault | CoordinateFrame::Ned => [a, b, c],
+            CoordinateFrame::Enu => [b, a, c.saturating_neg()],
+            CoordinateFrame::Nwu => [a, b.saturating_neg(), c.saturating_neg()],
+        }
+    }
+    /// Converts North-East-Down axis values to this frame
+    fn ned_to_frame(self, [n, e, d]: [i16; 3]) -> [i16; 3] {
+        match self {
+            CoordinateFrame::SensorDefault | CoordinateFrame::Ned => [n, e, d],
+            CoordinateFrame::Enu => [e, n, d.saturating_neg()],
+            CoordinateFrame::Nwu => [n, e.saturating_neg(), d.saturating_neg()],
+        }
+    }
+}
+
+/// Error for a raw axis value at the limits of the data registers
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct AxisLimitError {
+    /// Axis with the invalid value
+    pub axis: Axis,
+    /// Raw value of the axis
+    pub value: i16,
+}
+
+/// Point of a 16-point compass rose
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub enum CompassPoint {
+    /// North (N)
+    North,
+    /// North-northeast (NNE)
+    NorthNortheast,
+    /// Northeast (NE)
+    Northeast,
+    /// East-northeast (ENE)
+    EastNortheast,
+    /// East (E)
+    East,
+    /// East-southeast (ESE)
+    EastSoutheast,
+    /// Southeast (SE)
+    Southeast,
+    /// South-southeast (SSE)
+    SouthSoutheast,
+    /// South (S)
+    South,
+    /// South-southwest (SSW)
+    SouthSouthwest,
+    /// Southwest (SW)
+    Southwest,
+    /// West-southwest (WSW)
+    WestSouthwest,
+    /// West (W)
+    West,
+    /// West-northwest (WNW)
+    WestNorthwest,
+    /// Northwest (NW)
+    Northwest,
+    /// North-northwest (NNW)
+    NorthNorthwest,
+}
+
+impl CompassPoint {
+    /// All compass points clockwise from north
+    pub const ALL: [CompassPoint; 16] = [
+        CompassPoint::North,
+        CompassPoint::NorthNortheast,
+        CompassPoint::Northeast,
+        CompassPoint::EastNortheast,
+        CompassPoint::East,
+        CompassPoint::EastSoutheast,
+        CompassPoint::Southeast,
+        CompassPoint::SouthSoutheast,
+        CompassPoint::South,
+        CompassPoint::SouthSouthwest,
+        CompassPoint::Southwest,
+        CompassPoint::WestSouthwest,
+        CompassPoint::West,
+        CompassPoint::WestNorthwest,
+        CompassPoint::Northwest,
+        CompassPoint::NorthNorthwest,
+    ];
+    /// Returns the compass point nearest to a heading in degrees × 10 clockwise from north.
+    ///
+    /// Each point covers a 22.5° sector centered on its direction. Headings outside `0..3600`
+    /// are wrapped.
+    pub fn from_heading(heading: i32) -> CompassPoint {
+        let heading = heading.rem_euclid(3600) as usize;
+        CompassPoint::ALL[(heading * 2 + 225) / 450 % 16]
+    }
+    /// Returns the full name of the compass point, e.g. "North-northeast"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompassPoint::North => "North",
+            CompassPoint::NorthNortheast => "North-northeast",
+            CompassPoint::Northeast => "Northeast",
+            CompassPoint::EastNortheast => "East-northeast",
+            CompassPoint::East => "East",
+            CompassPoint::EastSoutheast => "East-southeast",
+            CompassPoint::Southeast => "Southeast",
+            CompassPoint::SouthSoutheast => "South-southeast",
+            CompassPoint::South => "South",
+            CompassPoint::SouthSouthwest => "South-southwest",
+            CompassPoint::Southwest => "Southwest",
+            CompassPoint::WestSouthwest => "West-southwest",
+            CompassPoint::West => "West",
+            CompassPoint::WestNorthwest => "West-northwest",
+            CompassPoint::Northwest => "Northwest",
+            CompassPoint::NorthNorthwest => "North-northwest",
+        }
+    }
+    /// Returns the abbreviation of the compass point, e.g. "NNE"
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            CompassPoint::North => "N",
+            CompassPoint::NorthNortheast => "NNE",
+            CompassPoint::Northeast => "NE",
+            CompassPoint::EastNortheast => "ENE",
+            CompassPoint::East => "E",
+            CompassPoint::EastSoutheast => "ESE",
+            CompassPoint::Southeast => "SE",
+            CompassPoint::SouthSoutheast => "SSE",
+            CompassPoint::South => "S",
+            CompassPoint::SouthSouthwest => "SSW",
+            CompassPoint::Southwest => "SW",
+            CompassPoint::WestSouthwest => "WSW",
+            CompassPoint::West => "W",
+            CompassPoint::WestNorthwest => "WNW",
+            CompassPoint::Northwest => "NW",
+            CompassPoint::NorthNorthwest => "NNW",
+        }
+    }
+}
+
+/// Measurement data
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub struct Measurement {
     /// X-axis (raw value)
     pub hx: i16,
@@ -145,6 +540,12 @@ pub struct Measurement {
 }
 
 impl Measurement {
+    const ZERO: Measurement = Measurement {
+        hx: 0,
+        hy: 0,
+        hz: 0,
+        flags: MeasurementFlags::empty(),
+    };
     /// X-axis (in nT)
     pub fn x_nanoteslas(&self) -> i32 {
         i32::from(self.hx) * SENSITIVITY_NT_PER_BIT
@@ -157,6 +558,177 @@ impl Measurement {
     pub fn z_nanoteslas(&self) -> i32 {
         i32::from(self.hz) * SENSITIVITY_NT_PER_BIT
     }
+    /// Creates a measurement from nanotesla (nT) values.
+    ///
+    /// The values are rounded to the nearest raw value and saturated to the range of raw values.
+    /// Flags are empty.
+    pub fn from_nanoteslas(x: i32, y: i32, z: i32) -> Measurement {
+        Measurement::from_scaled_nanoteslas([x, y, z].map(i64::from), 1)
+    }
+    /// Creates a measurement from microtesla (µT) values.
+    ///
+    /// The values are rounded to the nearest raw value and saturated to the range of raw values,
+    /// so e.g. 1 µT becomes the raw value 7 (1.05 µT). Flags are empty.
+    pub fn from_microteslas(x: i32, y: i32, z: i32) -> Measurement {
+        Measurement::from_scaled_nanoteslas([x, y, z].map(|value| i64::from(value) * 1000), 1)
+    }
+    /// Creates a measurement from fixed-point gauss (G) values, where each value is in units of
+    /// `1 / scale` G (e.g. a `scale` of 1000 means milligauss).
+    ///
+    /// The values are rounded to the nearest raw value and saturated to the range of raw values.
+    /// Flags are empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is zero.
+    pub fn from_gauss_scaled(x: i32, y: i32, z: i32, scale: i32) -> Measurement {
+        const NANOTESLAS_PER_GAUSS: i64 = 100_000;
+        Measurement::from_scaled_nanoteslas(
+            [x, y, z].map(|value| i64::from(value) * NANOTESLAS_PER_GAUSS),
+            i64::from(scale),
+        )
+    }
+    fn from_scaled_nanoteslas(values: [i64; 3], scale: i64) -> Measurement {
+        let divisor = i64::from(SENSITIVITY_NT_PER_BIT) * scale;
+        // round half away from zero
+        let raw = |value: i64| {
+            let half = divisor.abs() / 2 * value.signum();
+            saturate_i16((value + half) / divisor)
+        };
+        Measurement {
+            hx: raw(values[0]),
+            hy: raw(values[1]),
+            hz: raw(values[2]),
+            flags: MeasurementFlags::empty(),
+        }
+    }
+    /// Returns the raw axis values as 6 bytes (three little-endian `i16` values).
+    ///
+    /// This is a compact, deterministic binary representation for e.g. radio payloads. Flags are
+    /// not included.
+    pub fn to_le_bytes(&self) -> [u8; 6] {
+        let [x0, x1] = self.hx.to_le_bytes();
+        let [y0, y1] = self.hy.to_le_bytes();
+        let [z0, z1] = self.hz.to_le_bytes();
+        [x0, x1, y0, y1, z0, z1]
+    }
+    /// Creates a measurement from 6 bytes (three little-endian `i16` values).
+    ///
+    /// This is the inverse of [`to_le_bytes`](Self::to_le_bytes). Flags are empty.
+    pub fn from_le_bytes(bytes: [u8; 6]) -> Measurement {
+        Measurement::from(regs::axes_from_raw_data(bytes))
+    }
+    /// Returns the raw axis values as an array `[hx, hy, hz]`.
+    ///
+    /// The axes are separate public fields, so a reference to an array can't be returned without
+    /// changing the struct layout. The copy is cheap.
+    pub const fn to_array(&self) -> [i16; 3] {
+        [self.hx, self.hy, self.hz]
+    }
+    /// Creates a measurement from a register dump, with flags interpreted from `ST1` and `ST2`
+    /// in the same way as for measurements read by the driver
+    pub fn from_register_dump(dump: &regs::RegisterDump) -> Measurement {
+        Measurement::from_registers(dump.st1, dump.hx, dump.hy, dump.hz, dump.st2)
+    }
+    /// Returns the raw value of the given axis
+    pub fn get_axis(&self, axis: Axis) -> i16 {
+        match axis {
+            Axis::X => self.hx,
+            Axis::Y => self.hy,
+            Axis::Z => self.hz,
+        }
+    }
+    /// Sets the raw value of the given axis
+    pub fn set_axis(&mut self, axis: Axis, value: i16) {
+        match axis {
+            Axis::X => self.hx = value,
+            Axis::Y => self.hy = value,
+            Axis::Z => self.hz = value,
+        }
+    }
+    /// Combines two measurements, e.g. from redundant sensors, using the axes of `a` and the
+    /// union of the flags of both
+    pub fn merge_flags(a: Measurement, b: Measurement) -> Measurement {
+        Measurement {
+            flags: a.flags | b.flags,
+            ..a
+        }
+    }
+    /// Returns a measurement with only the given axis, and the other axes set to zero.
+    ///
+    /// Flags are kept as is.
+    pub fn project_onto_axis(&self, axis: Axis) -> Measurement {
+        let mut measurement = Measurement {
+            flags: self.flags,
+            ..Measurement::ZERO
+        };
+        measurement.set_axis(axis, self.get_axis(axis));
+        measurement
+    }
+    /// Returns the axis with the largest absolute raw value, and its raw value.
+    ///
+    /// Ties are broken in axis order, so X wins over Y and Y wins over Z.
+    pub const fn max_axis(&self) -> (Axis, i16) {
+        let mut result = (Axis::X, self.hx);
+        if self.hy.unsigned_abs() > result.1.unsigned_abs() {
+            result = (Axis::Y, self.hy);
+        }
+        if self.hz.unsigned_abs() > result.1.unsigned_abs() {
+            result = (Axis::Z, self.hz);
+        }
+        result
+    }
+    /// Returns the axis with the smallest absolute raw value, and its raw value.
+    ///
+    /// Ties are broken in axis order, so X wins over Y and Y wins over Z.
+    pub const fn min_axis(&self) -> (Axis, i16) {
+        let mut result = (Axis::X, self.hx);
+        if self.hy.unsigned_abs() < result.1.unsigned_abs() {
+            result = (Axis::Y, self.hy);
+        }
+        if self.hz.unsigned_abs() < result.1.unsigned_abs() {
+            result = (Axis::Z, self.hz);
+        }
+        result
+    }
+    /// Converts the measurement axes from one coordinate frame to another.
+    ///
+    /// The conversions only swap and negate axes. Negating `i16::MIN` saturates to `i16::MAX`.
+    pub fn into_frame(self, from: CoordinateFrame, to: CoordinateFrame) -> Measurement {
+        let [hx, hy, hz] = to.ned_to_frame(from.frame_to_ned(self.to_array()));
+        Measurement {
+            hx,
+            hy,
+            hz,
+            flags: self.flags,
+        }
+    }
+    /// Returns the axes in teslas (T)
+    pub fn to_tesla_f32(&self) -> [f32; 3] {
+        [
+            self.x_nanoteslas(),
+            self.y_nanoteslas(),
+            self.z_nanoteslas(),
+        ]
+        .map(|value| value as f32 * 1e-9)
+    }
+    /// Creates a measurement from tesla (T) values.
+    ///
+    /// The values are rounded to the nearest raw value and saturated to the range of raw values.
+    /// NaN values are converted to zero. Flags are empty.
+    pub fn from_tesla_f32(values: [f32; 3]) -> Measurement {
+        let [hx, hy, hz] = values.map(|value| {
+            let raw = value * 1e9 / SENSITIVITY_NT_PER_BIT as f32;
+            // `as` saturates, so only rounding is needed
+            (if raw < 0.0 { raw - 0.5 } else { raw + 0.5 }) as i16
+        });
+        Measurement {
+            hx,
+            hy,
+            hz,
+            flags: MeasurementFlags::empty(),
+        }
+    }
     /// Returns true if flags indicate data overrun has happened
     pub fn overrun(&self) -> bool {
         self.flags.contains(MeasurementFlags::OVERRUN)
@@ -165,18 +737,191 @@ impl Measurement {
     pub fn overflow(&self) -> bool {
         self.flags.contains(MeasurementFlags::OVERFLOW)
     }
+    /// Returns true if the X-axis raw value is at the limits of `i16`, which indicates clipping.
+    ///
+    /// This is checked independently of the overflow flag.
+    pub fn hx_saturated(&self) -> bool {
+        is_saturated(self.hx)
+    }
+    /// Returns true if the Y-axis raw value is at the limits of `i16`, which indicates clipping.
+    ///
+    /// This is checked independently of the overflow flag.
+    pub fn hy_saturated(&self) -> bool {
+        is_saturated(self.hy)
+    }
+    /// Returns true if the Z-axis raw value is at the limits of `i16`, which indicates clipping.
+    ///
+    /// This is checked independently of the overflow flag.
+    pub fn hz_saturated(&self) -> bool {
+        is_saturated(self.hz)
+    }
+    /// Heading in degrees × 10 clockwise from magnetic north, assuming an East-North-Up frame.
+    ///
+    /// The sensor X-axis is assumed to point to the right of the device and the Y-axis forward,
+    /// with the sensor mounted level. The heading is the direction of the Y-axis, in the range
+    /// `0..3600`. Returns None if both X and Y values are zero.
+    pub fn heading_enu(&self) -> Option<i32> {
+        heading_decidegrees(-i64::from(self.hx), i64::from(self.hy))
+    }
+    /// Heading in degrees × 10 clockwise from magnetic north, assuming a North-East-Down frame.
+    ///
+    /// The sensor X-axis is assumed to point forward and the Y-axis to the right of the device,
+    /// with the sensor mounted level. The heading is the direction of the X-axis, in the range
+    /// `0..3600`. Returns None if both X and Y values are zero.
+    pub fn heading_ned(&self) -> Option<i32> {
+        heading_decidegrees(-i64::from(self.hy), i64::from(self.hx))
+    }
+    /// Returns the nearest point of a 16-point compass rose for [`heading_ned`](Self::heading_ned).
+    ///
+    /// Returns None if both X and Y values are zero.
+    pub fn to_compass_point(&self) -> Option<CompassPoint> {
+        self.heading_ned().map(CompassPoint::from_heading)
+    }
+    /// Tilt-compensated heading in degrees × 10 clockwise from magnetic north.
+    ///
+    /// Uses the same North-East-Down mounting assumption as [`heading_ned`](Self::heading_ned),
+    /// but the sensor doesn't need to be level. The accelerometer values must use the same axes as
+    /// the magnetometer and read +1 g on an axis pointing up, so a level device reads about
+    /// `(0, 0, -1000)` mg. Only the direction of the acceleration matters, so any unit works.
+    ///
+    /// The heading is the direction of the X-axis projected onto the horizontal plane, in the range
+    /// `0..3600`. Returns None if the heading is undefined (e.g. the X-axis points straight up or
+    /// down).
+    pub fn tilt_compensated_heading(
+        &self,
+        accel_x: i32,
+        accel_y: i32,
+        accel_z: i32,
+    ) -> Option<i32> {
+        // down and magnetic field vectors
+        let d = [accel_x, accel_y, accel_z].map(|value| -i128::from(value));
+        let m = [self.hx, self.hy, self.hz].map(i128::from);
+        // east = down × magnetic field
+        let e = [
+            d[1] * m[2] - d[2] * m[1],
+            d[2] * m[0] - d[0] * m[2],
+            d[0] * m[1] - d[1] * m[0],
+        ];
+        // north = east × down, X component only. North is scaled by |down| compared to east
+        let down_len = isqrt(d.iter().map(|value| (value * value) as u64).sum()) as i128;
+        let mut east = e[0] * down_len;
+        let mut north = e[1] * d[2] - e[2] * d[1];
+        while east.abs() > 1 << 40 || north.abs() > 1 << 40 {
+            east >>= 1;
+            north >>= 1;
+        }
+        heading_decidegrees(east as i64, north as i64)
+    }
+    /// Dot product with another measurement (in nT²)
+    pub fn dot_product_nt(&self, other: &Measurement) -> i64 {
+        let (a, b) = (self.nanoteslas_i64(), other.nanoteslas_i64());
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+    /// Cross product with another measurement (in nT²)
+    pub fn cross_product_nt(&self, other: &Measurement) -> [i64; 3] {
+        let (a, b) = (self.nanoteslas_i64(), other.nanoteslas_i64());
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+    fn nanoteslas_i64(&self) -> [i64; 3] {
+        [self.hx, self.hy, self.hz]
+            .map(|value| i64::from(value) * i64::from(SENSITIVITY_NT_PER_BIT))
+    }
+    /// Returns the direction of the measurement as a Q15 fixed-point unit vector (32767 = 1.0).
+    ///
+    /// Returns `[0; 3]` if all axes are zero.
+    pub fn normalize_to_unit_q15(&self) -> [i16; 3] {
+        let axes = [self.hx, self.hy, self.hz].map(i64::from);
+        let sum_sq: i64 = axes.iter().map(|value| value * value).sum();
+        if sum_sq == 0 {
+            return [0; 3];
+        }
+        // magnitude scaled by 2^15 for precision with small values
+        let magnitude = isqrt((sum_sq as u64) << 30) as i64;
+        axes.map(|value| {
+            let q15 = ((value * i64::from(i16::MAX)) << 15) / magnitude;
+            q15.clamp(-i64::from(i16::MAX), i64::from(i16::MAX)) as i16
+        })
+    }
+    /// Scales the nanotesla values by a Q15 fixed-point gain factor (32767 = 1.0), e.g. for gain
+    /// correction.
+    ///
+    /// The result is rounded to the nearest raw value and saturated to the range of raw values.
+    /// Flags are kept as is.
+    pub fn scale_nt(&self, factor_q15: i16) -> Measurement {
+        let factor = i64::from(factor_q15);
+        let nanoteslas = self.nanoteslas_i64().map(|value| value * factor);
+        Measurement {
+            flags: self.flags,
+            ..Measurement::from_scaled_nanoteslas(nanoteslas, i64::from(i16::MAX))
+        }
+    }
+    /// Linearly interpolates between this measurement and another one.
+    ///
+    /// `t_q15` is a Q15 fixed-point fraction (32767 = 1.0), so 0 returns this measurement and
+    /// 32767 returns the other one. Negative values are treated as 0. The axes are rounded half
+    /// away from zero, and the flags of the result are the union of the flags of both.
+    pub fn interpolate(&self, other: &Measurement, t_q15: i16) -> Measurement {
+        let t = i64::from(t_q15.max(0));
+        let divisor = i64::from(i16::MAX);
+        let lerp = |a: i16, b: i16| {
+            let delta = (i64::from(b) - i64::from(a)) * t;
+            let half = divisor / 2 * delta.signum();
+            (i64::from(a) + (delta + half) / divisor) as i16
+        };
+        Measurement {
+            hx: lerp(self.hx, other.hx),
+            hy: lerp(self.hy, other.hy),
+            hz: lerp(self.hz, other.hz),
+            flags: self.flags | other.flags,
+        }
+    }
+    /// Returns true if all axes are within `tolerance_nt` nanoteslas of the other measurement.
+    ///
+    /// Flags are ignored in the comparison.
+    pub fn approx_eq(&self, other: &Measurement, tolerance_nt: i32) -> bool {
+        let within = |a: i32, b: i32| i64::from(a - b).abs() <= i64::from(tolerance_nt);
+        within(self.x_nanoteslas(), other.x_nanoteslas())
+            && within(self.y_nanoteslas(), other.y_nanoteslas())
+            && within(self.z_nanoteslas(), other.z_nanoteslas())
+    }
+    /// Checks that no raw axis value is at the limits of the 16-bit data registers.
+    ///
+    /// A value of ±32767 or beyond is outside the measurement range of the sensor, so it indicates
+    /// a faulty device or corrupted bus data even if the overflow flag is not set.
+    pub fn check_axis_limits(&self) -> Result<(), AxisLimitError> {
+        for axis in Axis::all() {
+            let value = self.get_axis(axis);
+            if value <= -i16::MAX || value == i16::MAX {
+                return Err(AxisLimitError { axis, value });
+            }
+        }
+        Ok(())
+    }
     #[inline]
     fn from_raw_data(st1: regs::St1, buffer: [u8; 8]) -> Measurement {
-        let st2 = regs::St2::from(buffer[7]);
+        Measurement::from_registers(
+            st1,
+            i16::from_le_bytes([buffer[0], buffer[1]]),
+            i16::from_le_bytes([buffer[2], buffer[3]]),
+            i16::from_le_bytes([buffer[4], buffer[5]]),
+            regs::St2::from(buffer[7]),
+        )
+    }
+    #[inline]
+    fn from_registers(st1: regs::St1, hx: i16, hy: i16, hz: i16, st2: regs::St2) -> Measurement {
         Measurement {
-            hx: i16::from_le_bytes([buffer[0], buffer[1]]),
-            hy: i16::from_le_bytes([buffer[2], buffer[3]]),
-            hz: i16::from_le_bytes([buffer[4], buffer[5]]),
-            flags: if st1.contains(regs::St1::DOR) {
+            hx,
+            hy,
+            hz,
+            flags: if st1.data_overrun() {
                 MeasurementFlags::OVERRUN
             } else {
                 MeasurementFlags::empty()
-            } | if st2.contains(regs::St2::HOFL) {
+            } | if st2.sensor_overflow() {
                 MeasurementFlags::OVERFLOW
             } else {
                 MeasurementFlags::empty()
@@ -185,6 +930,78 @@ impl Measurement {
     }
 }
 
+impl Index<Axis> for Measurement {
+    type Output = i16;
+
+    fn index(&self, axis: Axis) -> &i16 {
+        match axis {
+            Axis::X => &self.hx,
+            Axis::Y => &self.hy,
+            Axis::Z => &self.hz,
+        }
+    }
+}
+
+impl IndexMut<Axis> for Measurement {
+    fn index_mut(&mut self, axis: Axis) -> &mut i16 {
+        match axis {
+            Axis::X => &mut self.hx,
+            Axis::Y => &mut self.hy,
+            Axis::Z => &mut self.hz,
+        }
+    }
+}
+
+impl From<Measurement> for [i16; 3] {
+    fn from(measurement: Measurement) -> Self {
+        measurement.to_array()
+    }
+}
+
+impl Mul<i32> for Measurement {
+    type Output = Measurement;
+
+    /// Multiplies all axes by the given scalar, saturating to the range of raw values
+    fn mul(self, rhs: i32) -> Measurement {
+        let scale = |value: i16| saturate_i16(i64::from(value) * i64::from(rhs));
+        Measurement {
+            hx: scale(self.hx),
+            hy: scale(self.hy),
+            hz: scale(self.hz),
+            flags: self.flags,
+        }
+    }
+}
+
+impl Div<i32> for Measurement {
+    type Output = Measurement;
+
+    /// Divides all axes by the given scalar, saturating to the range of raw values.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `rhs` is zero.
+    fn div(self, rhs: i32) -> Measurement {
+        let scale = |value: i16| saturate_i16(i64::from(value) / i64::from(rhs));
+        Measurement {
+            hx: scale(self.hx),
+            hy: scale(self.hy),
+            hz: scale(self.hz),
+            flags: self.flags,
+        }
+    }
+}
+
+#[inline]
+fn saturate_i16(value: i64) -> i16 {
+    value.clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16
+}
+
+#[inline]
+fn is_saturated(value: i16) -> bool {
+    value == i16::MIN || value == i16::MAX
+}
+
 bitflags_macro! {
     /// Measurement flags
     #[repr(transparent)]
@@ -196,276 +1013,3298 @@ bitflags_macro! {
     }
 }
 
+impl MeasurementFlags {
+    /// Returns all flags that indicate data quality issues.
+    ///
+    /// New flags that indicate data quality issues will be included here, so this is the
+    /// forward-compatible way to check if a measurement has any issues.
+    pub const fn all_error_flags() -> MeasurementFlags {
+        MeasurementFlags::OVERFLOW.union(MeasurementFlags::OVERRUN)
+    }
+}
+
+impl fmt::Display for MeasurementFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("(none)");
+        }
+        let mut separator = "";
+        for (flag, name) in [
+            (MeasurementFlags::OVERFLOW, "OVERFLOW"),
+            (MeasurementFlags::OVERRUN, "OVERRUN"),
+        ] {
+            if self.contains(flag) {
+                write!(f, "{}{}", separator, name)?;
+                separator = "|";
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Result for a self-test
+///
+/// New fields may be added in minor releases, so `#[non_exhaustive]` prevents creating results
+/// with a struct literal. Results are created by the driver, or from a self-test measurement with
+/// the `From<Measurement>` implementation.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+#[non_exhaustive]
 pub struct SelfTestResult {
     /// Measurement data
     pub measurement: Measurement,
     /// True if self-test measurement data is valid and the test is passed
     pub is_valid: bool,
+    /// True if X-axis self-test measurement data is within the valid range
+    pub x_valid: bool,
+    /// True if Y-axis self-test measurement data is within the valid range
+    pub y_valid: bool,
+    /// True if Z-axis self-test measurement data is within the valid range
+    pub z_valid: bool,
+    /// Mode register value read back after switching to self-test mode, if it was checked
+    pub mode_read_back: Option<regs::ModeRegister>,
 }
 
 impl From<Measurement> for SelfTestResult {
     fn from(measurement: Measurement) -> Self {
-        let is_valid = (-200..=200).contains(&measurement.hx)
-            && (-200..=200).contains(&measurement.hy)
-            && (-1000..=-200).contains(&measurement.hz);
+        SelfTestResult::with_tolerance(
+            measurement,
+            SELF_TEST_TOLERANCE_X,
+            SELF_TEST_TOLERANCE_Y,
+            SELF_TEST_TOLERANCE_Z,
+        )
+    }
+}
+
+impl SelfTestResult {
+    /// Creates a result using per-axis tolerances from the center of the datasheet ranges
+    fn with_tolerance(measurement: Measurement, x_tol: i16, y_tol: i16, z_tol: i16) -> Self {
+        fn within(value: i16, min: i16, max: i16, tolerance: i16) -> bool {
+            let center = (i32::from(min) + i32::from(max)) / 2;
+            (i32::from(value) - center).abs() <= i32::from(tolerance)
+        }
+        let x_valid = within(measurement.hx, SELF_TEST_MIN_X, SELF_TEST_MAX_X, x_tol);
+        let y_valid = within(measurement.hy, SELF_TEST_MIN_Y, SELF_TEST_MAX_Y, y_tol);
+        let z_valid = within(measurement.hz, SELF_TEST_MIN_Z, SELF_TEST_MAX_Z, z_tol);
         Self {
             measurement,
-            is_valid,
+            is_valid: x_valid && y_valid && z_valid,
+            x_valid,
+            y_valid,
+            z_valid,
+            mode_read_back: None,
+        }
+    }
+    /// Creates a result that also requires the device to have been in self-test mode
+    fn with_mode_read_back(measurement: Measurement, mode: regs::ModeRegister) -> Self {
+        let result = SelfTestResult::from(measurement);
+        SelfTestResult {
+            is_valid: result.is_valid && mode == regs::ModeRegister::Mode(Mode::SelfTest),
+            mode_read_back: Some(mode),
+            ..result
         }
     }
 }
 
-/// Asynchronous API
-pub mod asynch {
-    use embedded_hal_async::{delay::DelayNs, i2c::I2c};
-
-    use crate::{
-        regs::{self, Register16, Register8, RegisterAddress},
-        Measurement, Mode, SelfTestResult, WhoIAm, I2C_ADDRESS, MODE_SET_WAIT_TIME_US,
-    };
+/// Report of a built-in test sequence
+///
+/// New fields may be added in minor releases, so `#[non_exhaustive]` prevents creating reports
+/// with a struct literal. Reports are created by the driver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct BuiltInTestReport {
+    /// Who I Am data read after the soft-reset
+    pub who_i_am: WhoIAm,
+    /// True if the Who I Am data matches AK09916
+    pub who_i_am_valid: bool,
+    /// Self-test result
+    pub self_test: SelfTestResult,
+    /// Measurements collected in continuous measurement mode 4 (100 Hz)
+    pub samples: [Measurement; 10],
+    /// True if none of the samples has the overflow flag set
+    pub samples_valid: bool,
+}
 
-    /// AK09916 driver
-    pub struct Ak09916<I: I2c, D: DelayNs> {
-        i2c: I,
-        delay: D,
+impl BuiltInTestReport {
+    /// Returns true if all steps of the test sequence passed
+    pub fn is_passed(&self) -> bool {
+        self.who_i_am_valid && self.self_test.is_valid && self.samples_valid
     }
+}
 
-    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
-        /// Creates a new asynchronous AK09916 driver
-        pub fn new(i2c: I, delay: D) -> Self {
-            Ak09916 { i2c, delay }
+/// Hard-iron calibration offsets
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct Calibration {
+    /// X-axis offset (raw value)
+    pub offset_x: i16,
+    /// Y-axis offset (raw value)
+    pub offset_y: i16,
+    /// Z-axis offset (raw value)
+    pub offset_z: i16,
+}
+
+impl Calibration {
+    /// Subtracts the offsets from the measurement, saturating to the range of raw values
+    pub fn apply(&self, measurement: Measurement) -> Measurement {
+        Measurement {
+            hx: measurement.hx.saturating_sub(self.offset_x),
+            hy: measurement.hy.saturating_sub(self.offset_y),
+            hz: measurement.hz.saturating_sub(self.offset_z),
+            flags: measurement.flags,
         }
-        /// Consumes the driver and releases resources used by it
-        pub fn release(self) -> (I, D) {
-            let Ak09916 { i2c, delay } = self;
-            (i2c, delay)
+    }
+    /// Returns the offsets as 6 bytes (three little-endian `i16` values) for e.g. storing in
+    /// non-volatile memory
+    pub fn to_bytes(&self) -> [u8; 6] {
+        let [x0, x1] = self.offset_x.to_le_bytes();
+        let [y0, y1] = self.offset_y.to_le_bytes();
+        let [z0, z1] = self.offset_z.to_le_bytes();
+        [x0, x1, y0, y1, z0, z1]
+    }
+    /// Creates a calibration from 6 bytes (three little-endian `i16` values).
+    ///
+    /// This is the inverse of [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: [u8; 6]) -> Calibration {
+        let [x0, x1, y0, y1, z0, z1] = bytes;
+        Calibration {
+            offset_x: i16::from_le_bytes([x0, x1]),
+            offset_y: i16::from_le_bytes([y0, y1]),
+            offset_z: i16::from_le_bytes([z0, z1]),
         }
-        /// Reads the Who I Am information from the device
-        pub async fn who_i_am(&mut self) -> Result<WhoIAm, I::Error> {
-            let mut buffer = [0, 0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)
-                .await?;
-            Ok(WhoIAm {
-                company_id: regs::Wia1::from(buffer[0]).0,
-                device_id: regs::Wia2::from(buffer[1]).0,
-            })
+    }
+}
+
+/// Driver state snapshot for e.g. saving to non-volatile memory before hibernation
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct DriverState {
+    /// Last mode written to the device, or None if unknown
+    pub mode: Option<Mode>,
+    /// Calibration stored in the driver
+    pub calibration: Option<Calibration>,
+}
+
+impl DriverState {
+    /// Returns the state as 8 bytes.
+    ///
+    /// The first byte is the mode register value (0xff if unknown), the second byte is 1 if
+    /// calibration is present, and the rest are the calibration bytes (zero if not present).
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[0] = self.mode.map_or(0xff, u8::from);
+        if let Some(calibration) = self.calibration {
+            bytes[1] = 1;
+            bytes[2..].copy_from_slice(&calibration.to_bytes());
         }
-        /// Polls the device for measurement data until it's available
-        pub async fn poll_measurement(
-            &mut self,
-            poll_interval_us: u32,
-        ) -> Result<Measurement, I::Error> {
-            let mut st1: regs::St1;
-            loop {
-                st1 = self.read_register8::<regs::St1>().await?;
-                if st1.contains(regs::St1::DRDY) {
-                    break;
-                }
-                self.delay.delay_us(poll_interval_us).await;
-            }
-            let mut buffer = [0; 8];
-            self.i2c.read(I2C_ADDRESS, &mut buffer).await?;
-            Ok(Measurement::from_raw_data(st1, buffer))
+        bytes
+    }
+    /// Creates a state from 8 bytes.
+    ///
+    /// This is the inverse of [`to_bytes`](Self::to_bytes). Returns None if the bytes are not
+    /// valid.
+    pub fn from_bytes(bytes: [u8; 8]) -> Option<DriverState> {
+        let [mode, has_calibration, x0, x1, y0, y1, z0, z1] = bytes;
+        let mode = match mode {
+            0xff => None,
+            mode => Some(Mode::try_from(mode).ok()?),
+        };
+        let calibration = match has_calibration {
+            0 => None,
+            1 => Some(Calibration::from_bytes([x0, x1, y0, y1, z0, z1])),
+            _ => return None,
+        };
+        Some(DriverState { mode, calibration })
+    }
+}
+
+/// Configuration for [`measure_with_config`](blocking::Ak09916::measure_with_config)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct MeasurementConfig<'a> {
+    /// Measurement mode
+    pub mode: Mode,
+    /// Delay between polls in μs
+    pub poll_interval_us: u32,
+    /// Maximum number of polls. The device is always polled at least once
+    pub max_polls: u32,
+    /// Calibration to apply to the measurement, if any
+    pub apply_calibration: Option<&'a Calibration>,
+}
+
+/// Fixed-capacity FIFO queue of measurements
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MeasurementQueue<const N: usize> {
+    buffer: [Measurement; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> MeasurementQueue<N> {
+    /// Creates a new empty queue
+    pub const fn new() -> Self {
+        MeasurementQueue {
+            buffer: [Measurement::ZERO; N],
+            head: 0,
+            len: 0,
         }
-        /// Reads the latest measurement data, if available.
-        ///
-        /// Returns None if measurement data is not ready
-        pub async fn read_measurement(&mut self) -> Result<Option<Measurement>, I::Error> {
-            let st1 = self.read_register8::<regs::St1>().await?;
-            if st1.contains(regs::St1::DRDY) {
-                let mut buffer = [0; 8];
-                self.i2c.read(I2C_ADDRESS, &mut buffer).await?;
-                Ok(Some(Measurement::from_raw_data(st1, buffer)))
-            } else {
-                Ok(None)
-            }
+    }
+    /// Adds a measurement to the back of the queue.
+    ///
+    /// Returns the measurement back as an error if the queue is full.
+    pub fn push_back(&mut self, measurement: Measurement) -> Result<(), Measurement> {
+        if self.is_full() {
+            return Err(measurement);
         }
-        /// Switches the device to the given mode
-        pub async fn switch_mode(&mut self, target_mode: Mode) -> Result<(), I::Error> {
-            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
-                .await?;
-            self.delay.delay_us(MODE_SET_WAIT_TIME_US).await;
-            self.write_register8(regs::Cntl2::from(target_mode)).await
+        self.buffer[(self.head + self.len) % N] = measurement;
+        self.len += 1;
+        Ok(())
+    }
+    /// Removes a measurement from the front of the queue, or returns None if the queue is empty
+    pub fn pop_front(&mut self) -> Option<Measurement> {
+        if self.is_empty() {
+            return None;
         }
-        /// Performs a self-test.
-        ///
-        /// The device switches to power-down mode automatically after the operation.
-        pub async fn self_test(&mut self) -> Result<SelfTestResult, I::Error> {
-            self.switch_mode(Mode::SelfTest).await?;
-            let measurement = self.poll_measurement(10).await?;
-            Ok(SelfTestResult::from(measurement))
+        let measurement = self.buffer[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(measurement)
+    }
+    /// Returns the number of measurements in the queue
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Returns true if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Returns true if the queue is full
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+    /// Returns an iterator over the measurements from front to back
+    pub fn iter(&self) -> impl Iterator<Item = &Measurement> + '_ {
+        (0..self.len).map(move |i| &self.buffer[(self.head + i) % N])
+    }
+    /// Adds a measurement to the back of the queue, removing the front one if the queue is full
+    fn push_back_overwrite(&mut self, measurement: Measurement) {
+        if self.is_full() {
+            self.pop_front();
         }
-        /// Performs a soft-reset.
-        ///
-        /// The device switches to power-down mode automatically after the operation.
-        pub async fn soft_reset(&mut self) -> Result<(), I::Error> {
-            self.write_register8(regs::Cntl3::SRST).await?;
-            loop {
-                self.delay.delay_us(MODE_SET_WAIT_TIME_US).await;
-                let cntl3 = self.read_register8::<regs::Cntl3>().await?;
-                if !cntl3.contains(regs::Cntl3::SRST) {
-                    break Ok(());
-                }
-            }
+        let _ = self.push_back(measurement);
+    }
+    /// Returns the mean of the measurements, or None if the queue is empty
+    fn mean(&self) -> Option<Measurement> {
+        let mut stats = MeasurementStats::new();
+        for measurement in self.iter() {
+            stats.push(*measurement);
         }
+        stats.mean()
     }
+}
 
-    /// Low-level register access API
-    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
-        /// Reads an 8-bit register
-        pub async fn read_register8<R: Register8>(&mut self) -> Result<R, I::Error> {
-            let mut buffer = [0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)
-                .await?;
-            Ok(R::from(buffer[0]))
+impl<const N: usize> Default for MeasurementQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "defmt-1")]
+impl<const N: usize> defmt::Format for MeasurementQueue<N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "MeasurementQueue {{ len: {} }}", self.len)
+    }
+}
+
+/// Streaming statistics accumulator for measurements
+///
+/// Computes the per-axis mean and variance of raw values without storing the samples.
+///
+/// This uses exact integer sums of values and squared values instead of Welford's algorithm.
+/// Welford's algorithm avoids cancellation with floating point numbers, but with integers it
+/// would need rounding on every step, while the sums are exact: with at most `u32::MAX` samples
+/// of at most 2^15 in magnitude, the sum of squares stays below 2^62 and fits in `i64`. The
+/// variance is computed from the sums with `i128` intermediates. Measurements pushed after
+/// `u32::MAX` samples are ignored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct MeasurementStats {
+    count: u32,
+    sum: [i64; 3],
+    sum_sq: [i64; 3],
+    flags: MeasurementFlags,
+}
+
+impl MeasurementStats {
+    /// Creates a new empty accumulator
+    pub const fn new() -> Self {
+        MeasurementStats {
+            count: 0,
+            sum: [0; 3],
+            sum_sq: [0; 3],
+            flags: MeasurementFlags::empty(),
         }
-        /// Reads a 16-bit register
-        pub async fn read_register16<R: Register16>(&mut self) -> Result<R, I::Error> {
-            let mut buffer = [0, 0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)
-                .await?;
-            Ok(R::from(i16::from_le_bytes(buffer)))
+    }
+    /// Adds a measurement to the statistics.
+    ///
+    /// The measurement is ignored if `u32::MAX` measurements have already been added.
+    pub fn push(&mut self, m: Measurement) {
+        let Some(count) = self.count.checked_add(1) else {
+            return;
+        };
+        self.count = count;
+        for (i, value) in [m.hx, m.hy, m.hz].into_iter().enumerate() {
+            let value = i64::from(value);
+            self.sum[i] += value;
+            self.sum_sq[i] += value * value;
         }
-        /// Writes a 8-bit register
-        pub async fn write_register8<R: Register8>(&mut self, register: R) -> Result<(), I::Error> {
-            let buffer = [u8::from(R::ADDRESS), register.into()];
-            self.i2c.write(I2C_ADDRESS, &buffer).await
+        self.flags |= m.flags;
+    }
+    /// Returns the number of measurements added so far
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    /// Returns the mean of all measurements, or None if there are no measurements.
+    ///
+    /// The flags of the result are the union of the flags of all measurements.
+    pub fn mean(&self) -> Option<Measurement> {
+        if self.count == 0 {
+            return None;
         }
-        /// Dumps all non-reserved register data
-        pub async fn dump_registers(&mut self) -> Result<regs::RegisterDump, I::Error> {
-            let mut buffer = [0; 16];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)
-                .await?;
-            Ok(regs::RegisterDump::from_raw_data(buffer))
+        let count = i64::from(self.count);
+        let mean = |i: usize| self.sum[i].div_euclid(count) as i16;
+        Some(Measurement {
+            hx: mean(0),
+            hy: mean(1),
+            hz: mean(2),
+            flags: self.flags,
+        })
+    }
+    /// X-axis population variance (raw value²)
+    pub fn variance_x(&self) -> i64 {
+        self.variance(0)
+    }
+    /// Y-axis population variance (raw value²)
+    pub fn variance_y(&self) -> i64 {
+        self.variance(1)
+    }
+    /// Z-axis population variance (raw value²)
+    pub fn variance_z(&self) -> i64 {
+        self.variance(2)
+    }
+    /// X-axis standard deviation, rounded down to an integer (raw value)
+    pub fn std_dev_approx_x(&self) -> i32 {
+        isqrt(self.variance_x() as u64) as i32
+    }
+    /// Y-axis standard deviation, rounded down to an integer (raw value)
+    pub fn std_dev_approx_y(&self) -> i32 {
+        isqrt(self.variance_y() as u64) as i32
+    }
+    /// Z-axis standard deviation, rounded down to an integer (raw value)
+    pub fn std_dev_approx_z(&self) -> i32 {
+        isqrt(self.variance_z() as u64) as i32
+    }
+    fn variance(&self, i: usize) -> i64 {
+        if self.count == 0 {
+            return 0;
         }
+        let count = i128::from(self.count);
+        let sum = i128::from(self.sum[i]);
+        let sum_sq = i128::from(self.sum_sq[i]);
+        ((count * sum_sq - sum * sum) / (count * count)) as i64
     }
 }
 
-/// Blocking API
-pub mod blocking {
-    use embedded_hal::{delay::DelayNs, i2c::I2c};
+impl Default for MeasurementStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Angle of (x, y) in millidegrees in the range `-180_000..=180_000`, computed with CORDIC.
+///
+/// Returns 0 for (0, 0).
+fn atan2_millidegrees(y: i64, x: i64) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    // atan(2^-i) in millidegrees
+    const ATAN_TABLE: [i64; 16] = [
+        45000, 26565, 14036, 7125, 3576, 1790, 895, 448, 224, 112, 56, 28, 14, 7, 3, 2,
+    ];
+    let (mut x, mut y, mut angle) = if x < 0 {
+        (-x << 16, -y << 16, if y < 0 { -180_000 } else { 180_000 })
+    } else {
+        (x << 16, y << 16, 0)
+    };
+    for (i, step) in ATAN_TABLE.iter().enumerate() {
+        let (dx, dy) = (y >> i, x >> i);
+        if y > 0 {
+            x += dx;
+            y -= dy;
+            angle += step;
+        } else {
+            x -= dx;
+            y += dy;
+            angle -= step;
+        }
+    }
+    angle.clamp(-180_000, 180_000) as i32
+}
+
+/// Heading in degrees × 10 in the range `0..3600` from the north and east components
+fn heading_decidegrees(east: i64, north: i64) -> Option<i32> {
+    if east == 0 && north == 0 {
+        return None;
+    }
+    let millidegrees = atan2_millidegrees(east, north).rem_euclid(360_000);
+    Some(((millidegrees + 50) / 100) % 3600)
+}
+
+/// Integer square root, rounded down
+fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = (x + value / x) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// Blocking magnetometer interface for code that supports multiple sensors
+///
+/// The trait is object safe, so it can be used as e.g. `&mut dyn MagSensor<Error = E>`.
+pub trait MagSensor {
+    /// Error type
+    type Error;
+    /// Reads the latest measurement data, if available
+    fn read_measurement(&mut self) -> Result<Option<Measurement>, Self::Error>;
+    /// Switches the sensor to the given mode
+    fn switch_mode(&mut self, mode: Mode) -> Result<(), Self::Error>;
+}
+
+/// Asynchronous API
+pub mod asynch {
+    use core::fmt;
+    use embedded_hal::digital::InputPin;
+    use embedded_hal_async::{delay::DelayNs, i2c::I2c};
 
     use crate::{
+        inner::{self, Ak09916Inner},
         regs::{self, Register16, Register8, RegisterAddress},
-        Measurement, Mode, SelfTestResult, WhoIAm, I2C_ADDRESS, MODE_SET_WAIT_TIME_US,
+        Ak09916Error, BuiltInTestReport, Calibration, DriverState, Measurement, MeasurementConfig,
+        MeasurementFlags, MeasurementQueue, Mode, SelfTestResult, WhoIAm, I2C_ADDRESS,
+        MODE_POWER_ON_RESET_WAIT_TIME_US, MODE_SET_WAIT_TIME_US, SELF_TEST_TOLERANCE_X,
+        SELF_TEST_TOLERANCE_Y, SELF_TEST_TOLERANCE_Z,
     };
 
     /// AK09916 driver
+    ///
+    /// The driver can be cloned if the I²C bus and delay can be cloned. The clone is an
+    /// independent driver instance with a copy of the driver state, and whether the bus state is
+    /// shared depends on the bus type. Cloning doesn't add any locking.
+    #[derive(Clone)]
     pub struct Ak09916<I: I2c, D: DelayNs> {
-        i2c: I,
-        delay: D,
+        inner: Ak09916Inner<I, D>,
     }
 
     impl<I: I2c, D: DelayNs> Ak09916<I, D> {
-        /// Creates a new blocking AK09916 driver
+        /// Creates a new asynchronous AK09916 driver
         pub fn new(i2c: I, delay: D) -> Self {
-            Ak09916 { i2c, delay }
+            Ak09916 {
+                inner: Ak09916Inner::new(i2c, delay),
+            }
+        }
+        /// Creates a new asynchronous AK09916 driver with the given stored calibration.
+        ///
+        /// This is the inverse of [`into_parts`](Self::into_parts). The calibration is not applied
+        /// to measurements automatically.
+        pub fn from_parts(i2c: I, delay: D, calibration: Option<Calibration>) -> Self {
+            Ak09916 {
+                inner: Ak09916Inner::from_parts(i2c, delay, calibration),
+            }
+        }
+        /// Consumes the driver and returns the I²C bus and delay used by it
+        pub fn into_inner(self) -> (I, D) {
+            self.inner.release()
         }
         /// Consumes the driver and releases resources used by it
+        #[deprecated(note = "use `into_inner` instead")]
         pub fn release(self) -> (I, D) {
-            let Ak09916 { i2c, delay } = self;
-            (i2c, delay)
+            self.into_inner()
+        }
+        /// Consumes the driver and returns the resources used by it and the stored calibration
+        pub fn into_parts(self) -> (I, D, Option<Calibration>) {
+            self.inner.into_parts()
+        }
+        /// Returns a recommended [`poll_measurement`](Self::poll_measurement) interval in μs for
+        /// the given mode.
+        ///
+        /// This is a tenth of the measurement period for continuous measurement modes, and 1 ms
+        /// for other modes.
+        pub fn poll_interval_for_mode(mode: Mode) -> u32 {
+            inner::poll_interval_for_mode(mode)
+        }
+        /// Returns the last mode written to the device, without doing any I²C communication.
+        ///
+        /// Returns None if the mode is unknown, e.g. before the first mode switch or after a
+        /// soft-reset. Single measurement and self-test modes are never returned, because the
+        /// device switches to power-down mode automatically after the measurement. Writes done
+        /// through [`borrow_i2c_mut`](Self::borrow_i2c_mut) are not tracked.
+        pub fn current_mode(&self) -> Option<Mode> {
+            self.inner.current_mode
+        }
+        /// Replaces the calibration stored in the driver
+        pub fn set_calibration(&mut self, calibration: Option<Calibration>) {
+            self.inner.calibration = calibration;
+        }
+        /// Returns the calibration stored in the driver, if any
+        pub fn get_calibration(&self) -> Option<&Calibration> {
+            self.inner.calibration.as_ref()
+        }
+        /// Returns a snapshot of the driver state.
+        ///
+        /// No I²C communication is done, so the mode is None if it's unknown.
+        pub fn save_state(&self) -> DriverState {
+            DriverState {
+                mode: self.inner.current_mode,
+                calibration: self.inner.calibration,
+            }
+        }
+        /// Restores a driver state snapshot, switching the device to the saved mode if it's known.
+        ///
+        /// The device may have been power cycled since the snapshot was taken, so the cached mode
+        /// is cleared and the saved mode is always written, even if the cached mode matches it.
+        pub async fn restore_state(&mut self, state: DriverState) -> Result<(), I::Error> {
+            self.inner.calibration = state.calibration;
+            self.inner.current_mode = None;
+            match state.mode {
+                Some(mode) => self.switch_mode(mode).await,
+                None => Ok(()),
+            }
+        }
+        /// Sets a callback that is called with measurements that have the overflow flag set.
+        ///
+        /// The callback is called by [`poll_measurement`](Self::poll_measurement) and
+        /// [`read_measurement`](Self::read_measurement) before returning the measurement, so it's
+        /// also called by the methods and continuous iterators built on them. Reads that bypass
+        /// them don't call it: [`read_measurement_unchecked`](Self::read_measurement_unchecked),
+        /// [`read_into_buffer`](Self::read_into_buffer), reads triggered by the DRDY pin in
+        /// [`poll_measurement_nb`](Self::poll_measurement_nb), and
+        /// [`MeasurementReady::consume_measurement`].
+        ///
+        /// The callback is a plain function pointer, so it can't capture any state. Use e.g. a
+        /// static atomic to pass information out of it. Pass None to remove the callback.
+        pub fn on_overflow(&mut self, callback: Option<fn(&Measurement)>) {
+            self.inner.overflow_callback = callback;
+        }
+        /// Sets the poll interval in μs for [`poll_measurement_auto`](Self::poll_measurement_auto)
+        pub fn set_poll_interval(&mut self, poll_us: u32) {
+            self.inner.poll_interval_us = Some(poll_us);
+        }
+        /// Returns the poll interval in μs used by
+        /// [`poll_measurement_auto`](Self::poll_measurement_auto).
+        ///
+        /// If no interval has been set, this is the recommended interval for the current mode
+        /// according to [`poll_interval_for_mode`](Self::poll_interval_for_mode).
+        pub fn get_poll_interval(&self) -> u32 {
+            self.inner.poll_interval_us()
+        }
+        /// Returns a mutable reference to the I²C bus for temporary use by other code.
+        ///
+        /// Any transactions using the bus must be finished before any driver method is called again.
+        pub fn borrow_i2c_mut(&mut self) -> &mut I {
+            &mut self.inner.i2c
+        }
+        /// Checks if the device acknowledges its address by doing a zero-length write.
+        ///
+        /// Returns false if the device doesn't acknowledge, and an error for other I²C errors
+        pub async fn is_connected(&mut self) -> Result<bool, I::Error> {
+            match self.inner.i2c.write(I2C_ADDRESS, &[]).await {
+                Ok(()) => Ok(true),
+                Err(err) if inner::is_nack(&err) => Ok(false),
+                Err(err) => Err(err),
+            }
         }
         /// Reads the Who I Am information from the device
-        pub fn who_i_am(&mut self) -> Result<WhoIAm, I::Error> {
-            let mut buffer = [0, 0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)?;
-            Ok(WhoIAm {
-                company_id: regs::Wia1::from(buffer[0]).0,
-                device_id: regs::Wia2::from(buffer[1]).0,
-            })
+        pub async fn who_i_am(&mut self) -> Result<WhoIAm, I::Error> {
+            let buffer = self.read_wia_raw().await?;
+            Ok(inner::who_i_am_from_raw_data(buffer))
+        }
+        /// Waits for the device to become ready for I²C communication, e.g. after power-up.
+        ///
+        /// The driver waits [`MODE_POWER_ON_RESET_WAIT_TIME_US`] first, and then the device IDs are
+        /// read. The read is retried up to `retry_count` times with `retry_delay_us` μs between
+        /// attempts. Returns [`Ak09916Error::DeviceNotFound`] if all attempts fail.
+        pub async fn wait_for_device(
+            &mut self,
+            retry_count: u32,
+            retry_delay_us: u32,
+        ) -> Result<WhoIAm, Ak09916Error<I::Error>> {
+            self.inner
+                .delay
+                .delay_us(MODE_POWER_ON_RESET_WAIT_TIME_US)
+                .await;
+            self.who_i_am_retry(retry_count.saturating_add(1), retry_delay_us)
+                .await
+                .map_err(|_| Ak09916Error::DeviceNotFound)
+        }
+        /// Reads the device IDs, retrying on I²C errors, and checks that the device is AK09916.
+        ///
+        /// The read is attempted up to `max_attempts` times (at least once) with `delay_us` μs
+        /// between attempts. Returns the I²C error of the last attempt if all attempts fail, or
+        /// [`Ak09916Error::WrongDevice`] if the device IDs don't match.
+        pub async fn read_who_i_am_loop(
+            &mut self,
+            max_attempts: u32,
+            delay_us: u32,
+        ) -> Result<WhoIAm, Ak09916Error<I::Error>> {
+            let who_i_am = self
+                .who_i_am_retry(max_attempts, delay_us)
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            if who_i_am == WhoIAm::AK09916 {
+                Ok(who_i_am)
+            } else {
+                Err(Ak09916Error::WrongDevice(who_i_am))
+            }
+        }
+        /// Reads the device IDs up to `max_attempts` times (at least once) with `delay_us` μs
+        /// between attempts, returning the I²C error of the last attempt if all attempts fail
+        async fn who_i_am_retry(
+            &mut self,
+            max_attempts: u32,
+            delay_us: u32,
+        ) -> Result<WhoIAm, I::Error> {
+            let mut attempts = 1;
+            loop {
+                match self.who_i_am().await {
+                    Ok(who_i_am) => break Ok(who_i_am),
+                    Err(err) if attempts >= max_attempts => break Err(err),
+                    Err(_) => {
+                        attempts += 1;
+                        self.inner.delay.delay_us(delay_us).await;
+                    }
+                }
+            }
         }
         /// Polls the device for measurement data until it's available
-        pub fn poll_measurement(&mut self, poll_interval_us: u32) -> Result<Measurement, I::Error> {
+        pub async fn poll_measurement(
+            &mut self,
+            poll_interval_us: u32,
+        ) -> Result<Measurement, I::Error> {
             let mut st1: regs::St1;
             loop {
-                st1 = self.read_register8::<regs::St1>()?;
-                if st1.contains(regs::St1::DRDY) {
+                st1 = self.read_register8::<regs::St1>().await?;
+                if st1.data_ready() {
                     break;
                 }
-                self.delay.delay_us(poll_interval_us);
+                self.inner.delay.delay_us(poll_interval_us).await;
             }
             let mut buffer = [0; 8];
-            self.i2c.read(I2C_ADDRESS, &mut buffer)?;
-            Ok(Measurement::from_raw_data(st1, buffer))
+            self.inner.i2c.read(I2C_ADDRESS, &mut buffer).await?;
+            Ok(self
+                .inner
+                .check_overflow(Measurement::from_raw_data(st1, buffer)))
+        }
+        /// Polls the device for measurement data until it's available, using the poll interval
+        /// returned by [`get_poll_interval`](Self::get_poll_interval)
+        pub async fn poll_measurement_auto(&mut self) -> Result<Measurement, I::Error> {
+            let poll_interval_us = self.get_poll_interval();
+            self.poll_measurement(poll_interval_us).await
         }
         /// Reads the latest measurement data, if available.
         ///
         /// Returns None if measurement data is not ready
-        pub fn read_measurement(&mut self) -> Result<Option<Measurement>, I::Error> {
-            let st1 = self.read_register8::<regs::St1>()?;
-            if st1.contains(regs::St1::DRDY) {
+        pub async fn read_measurement(&mut self) -> Result<Option<Measurement>, I::Error> {
+            let st1 = self.read_register8::<regs::St1>().await?;
+            if st1.data_ready() {
                 let mut buffer = [0; 8];
-                self.i2c.read(I2C_ADDRESS, &mut buffer)?;
-                Ok(Some(Measurement::from_raw_data(st1, buffer)))
+                self.inner.i2c.read(I2C_ADDRESS, &mut buffer).await?;
+                let measurement = Measurement::from_raw_data(st1, buffer);
+                Ok(Some(self.inner.check_overflow(measurement)))
             } else {
                 Ok(None)
             }
         }
-        /// Switches the device to the given mode
-        pub fn switch_mode(&mut self, target_mode: Mode) -> Result<(), I::Error> {
-            self.write_register8(regs::Cntl2::from(Mode::PowerDown))?;
-            self.delay.delay_us(MODE_SET_WAIT_TIME_US);
-            self.write_register8(regs::Cntl2::from(target_mode))
+        /// Reads the latest measurement data if the DRDY pin signals that it's available.
+        ///
+        /// Returns None without any I²C transactions if the pin is low, which saves bus bandwidth
+        /// compared to polling `ST1`. If the pin can't be read, `ST1` is checked instead.
+        pub async fn poll_measurement_nb<P: InputPin>(
+            &mut self,
+            drdy_pin: &mut P,
+        ) -> Result<Option<Measurement>, I::Error> {
+            match drdy_pin.is_high() {
+                Ok(false) => Ok(None),
+                Ok(true) => {
+                    let [st1, data @ ..] = self.read_measurement_raw().await?;
+                    Ok(Some(Measurement::from_raw_data(regs::St1::from(st1), data)))
+                }
+                Err(_) => self.read_measurement().await,
+            }
         }
-        /// Performs a self-test.
+        /// Reads the latest measurement data, or returns the previous one if new data is not ready.
         ///
-        /// The device switches to power-down mode automatically after the operation.
-        pub fn self_test(&mut self) -> Result<SelfTestResult, I::Error> {
-            self.switch_mode(Mode::SelfTest)?;
-            let measurement = self.poll_measurement(10)?;
-            Ok(SelfTestResult::from(measurement))
+        /// A previous measurement is returned with the overrun flag set. If this method hasn't
+        /// returned fresh data before, the data registers are read without checking if they're
+        /// ready, and the overrun flag is set.
+        pub async fn read_measurement_or_stale(&mut self) -> Result<Measurement, I::Error> {
+            if let Some(measurement) = self.read_measurement().await? {
+                self.inner.last_measurement = Some(measurement);
+                return Ok(measurement);
+            }
+            let mut measurement = match self.inner.last_measurement {
+                Some(measurement) => measurement,
+                None => self.read_measurement_unchecked().await?,
+            };
+            measurement.flags.insert(MeasurementFlags::OVERRUN);
+            Ok(measurement)
         }
-        /// Performs a soft-reset.
+        /// Checks if measurement data is ready, returning a handle for reading it.
         ///
-        /// The device switches to power-down mode automatically after the operation.
-        pub fn soft_reset(&mut self) -> Result<(), I::Error> {
-            self.write_register8(regs::Cntl3::SRST)?;
+        /// The handle borrows the driver, so no other transactions can happen before the
+        /// measurement is read, and reading it never returns None.
+        pub async fn data_ready(&mut self) -> Result<Option<MeasurementReady<'_, I, D>>, I::Error> {
+            let st1 = self.read_register8::<regs::St1>().await?;
+            Ok(if st1.data_ready() {
+                Some(MeasurementReady { driver: self, st1 })
+            } else {
+                None
+            })
+        }
+        /// Reads the latest measurement data, retrying up to `max_retries` times if it's not ready.
+        ///
+        /// The driver waits `poll_us` μs before each retry. Returns None if measurement data was
+        /// not ready after all retries.
+        pub async fn read_measurement_retry(
+            &mut self,
+            max_retries: u32,
+            poll_us: u32,
+        ) -> Result<Option<Measurement>, I::Error> {
+            let mut retries = 0;
             loop {
-                self.delay.delay_us(MODE_SET_WAIT_TIME_US);
-                let cntl3 = self.read_register8::<regs::Cntl3>()?;
-                if !cntl3.contains(regs::Cntl3::SRST) {
-                    break Ok(());
+                if let Some(measurement) = self.read_measurement().await? {
+                    break Ok(Some(measurement));
                 }
+                if retries >= max_retries {
+                    break Ok(None);
+                }
+                retries += 1;
+                self.inner.delay.delay_us(poll_us).await;
             }
         }
-    }
-
-    /// Low-level register access API
-    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
-        /// Reads an 8-bit register
-        pub fn read_register8<R: Register8>(&mut self) -> Result<R, I::Error> {
-            let mut buffer = [0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)?;
-            Ok(R::from(buffer[0]))
+        /// Reads measurement data without checking if it's ready.
+        ///
+        /// This skips reading `ST1`, so the measurement is read in a single transaction, but the data
+        /// may be stale and the data overrun flag is never set. This is only safe if the host
+        /// guarantees that new data is available, e.g. by reading at a slower rate than the
+        /// continuous measurement mode frequency. The [`on_overflow`](Self::on_overflow) callback
+        /// isn't called.
+        pub async fn read_measurement_unchecked(&mut self) -> Result<Measurement, I::Error> {
+            let mut buffer = [0; 8];
+            self.read_into_buffer(&mut buffer).await
         }
-        /// Reads a 16-bit register
-        pub fn read_register16<R: Register16>(&mut self) -> Result<R, I::Error> {
-            let mut buffer = [0, 0];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)?;
-            Ok(R::from(i16::from_le_bytes(buffer)))
+        /// Reads measurement data without checking if it's ready, using a caller-owned buffer.
+        ///
+        /// This is the same as [`read_measurement_unchecked`](Self::read_measurement_unchecked),
+        /// but the buffer is passed directly to the I²C bus, which is useful for e.g. DMA-backed
+        /// I²C implementations. Afterwards the buffer contains the raw `HXL`..`HZH`, `TMPS`, and
+        /// `ST2` register data. The [`on_overflow`](Self::on_overflow) callback isn't called.
+        pub async fn read_into_buffer(
+            &mut self,
+            buf: &mut [u8; 8],
+        ) -> Result<Measurement, I::Error> {
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Hxl)], buf)
+                .await?;
+            Ok(Measurement::from_raw_data(regs::St1::empty(), *buf))
         }
-        /// Writes a 8-bit register
-        pub fn write_register8<R: Register8>(&mut self, register: R) -> Result<(), I::Error> {
-            let buffer = [u8::from(R::ADDRESS), register.into()];
-            self.i2c.write(I2C_ADDRESS, &buffer)
+        /// Polls the device for measurement data until it's available or the time budget runs out.
+        ///
+        /// The elapsed time is estimated from the poll delays, so time spent in I²C transactions is
+        /// not included. Returns None if no measurement data was available within `deadline_us` μs.
+        pub async fn read_measurement_blocking_until(
+            &mut self,
+            deadline_us: u32,
+            poll_interval_us: u32,
+        ) -> Result<Option<Measurement>, I::Error> {
+            let mut elapsed_us = 0;
+            loop {
+                if let Some(measurement) = self.read_measurement().await? {
+                    break Ok(Some(measurement));
+                }
+                match inner::next_poll_delay(elapsed_us, deadline_us, poll_interval_us) {
+                    Some(delay_us) => {
+                        self.inner.delay.delay_us(delay_us).await;
+                        elapsed_us += delay_us;
+                    }
+                    None => break Ok(None),
+                }
+            }
         }
-        /// Dumps all non-reserved register data
-        pub fn dump_registers(&mut self) -> Result<regs::RegisterDump, I::Error> {
-            let mut buffer = [0; 16];
-            self.i2c
-                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)?;
-            Ok(regs::RegisterDump::from_raw_data(buffer))
+        /// Switches the device to the given mode and collects exactly `N` measurements.
+        ///
+        /// The device is left in the given mode.
+        pub async fn fill_queue<const N: usize>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> Result<MeasurementQueue<N>, I::Error> {
+            self.switch_mode(mode).await?;
+            let mut queue = MeasurementQueue::new();
+            while !queue.is_full() {
+                let measurement = self.poll_measurement(poll_us).await?;
+                let _ = queue.push_back(measurement);
+            }
+            Ok(queue)
+        }
+        /// Switches the device to the given mode, collects exactly `N` measurements, and switches
+        /// the device to power-down mode.
+        pub async fn continuous_read_n<const N: usize>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> Result<[Measurement; N], I::Error> {
+            self.switch_mode(mode).await?;
+            let mut measurements = [Measurement::ZERO; N];
+            for measurement in measurements.iter_mut() {
+                *measurement = self.poll_measurement(poll_us).await?;
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .await?;
+            Ok(measurements)
         }
+        /// Switches the device to the given mode and passes each measurement to `f` until it
+        /// returns false.
+        ///
+        /// The device is switched to power-down mode before returning, also if reading a
+        /// measurement fails.
+        pub async fn continuous_poll_fn<F: FnMut(Measurement) -> bool>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+            mut f: F,
+        ) -> Result<(), I::Error> {
+            self.switch_mode(mode).await?;
+            let result = loop {
+                match self.poll_measurement(poll_us).await {
+                    Ok(measurement) if f(measurement) => (),
+                    Ok(_) => break Ok(()),
+                    Err(err) => break Err(err),
+                }
+            };
+            let power_down = self
+                .write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .await;
+            result.and(power_down)
+        }
+        /// Does `warmup_count` single measurements, discarding the results, and then switches the
+        /// device to the given continuous measurement mode.
+        ///
+        /// The device is switched to power-down mode with the required wait time before every
+        /// mode switch.
+        pub async fn single_then_continuous(
+            &mut self,
+            warmup_count: u32,
+            continuous_mode: Mode,
+        ) -> Result<(), I::Error> {
+            for _ in 0..warmup_count {
+                self.switch_mode(Mode::SingleMeasurement).await?;
+                self.poll_measurement(10).await?;
+            }
+            self.switch_mode(continuous_mode).await
+        }
+        /// Switches the device to the given mode.
+        ///
+        /// Nothing is written if the device is known to be in the given mode already.
+        pub async fn switch_mode(&mut self, target_mode: Mode) -> Result<(), I::Error> {
+            if self.inner.current_mode == Some(target_mode) {
+                return Ok(());
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .await?;
+            self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US).await;
+            self.write_register8(regs::Cntl2::from(target_mode)).await
+        }
+        /// Switches the device to the given mode, skipping the power-down step if possible.
+        ///
+        /// The mode register is read first unless the current mode is known, and the device is only
+        /// switched to power-down mode if it's not already in power-down mode. The required wait
+        /// time is always observed before the new mode is written, because the device may have
+        /// been switched to power-down mode just before.
+        pub async fn reconfigure_mode(&mut self, new_mode: Mode) -> Result<(), I::Error> {
+            if new_mode == Mode::PowerDown {
+                return self.power_down().await;
+            }
+            if self.inner.current_mode == Some(new_mode) {
+                return Ok(());
+            }
+            let powered_down = match self.inner.current_mode {
+                Some(mode) => mode == Mode::PowerDown,
+                None => self.read_register8::<regs::Cntl2>().await?.is_power_down(),
+            };
+            if !powered_down {
+                self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                    .await?;
+            }
+            self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US).await;
+            self.write_register8(regs::Cntl2::from(new_mode)).await
+        }
+        /// Switches the device to power-down mode.
+        ///
+        /// The mode register is read first unless the current mode is known, and nothing is written
+        /// if the device is already in power-down mode.
+        pub async fn power_down(&mut self) -> Result<(), I::Error> {
+            if self.inner.current_mode == Some(Mode::PowerDown) {
+                return Ok(());
+            }
+            let cntl2 = self.read_register8::<regs::Cntl2>().await?;
+            if cntl2.is_power_down() {
+                self.inner.set_mode(Some(Mode::PowerDown));
+                return Ok(());
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .await
+        }
+        /// Switches the device to the given mode and verifies it by reading back the mode register.
+        ///
+        /// Note that single measurement and self-test modes switch to power-down mode automatically
+        /// after the measurement, so the read back mode may not match if the measurement is
+        /// already finished.
+        pub async fn checked_switch_mode(
+            &mut self,
+            target_mode: Mode,
+        ) -> Result<(), Ak09916Error<I::Error>> {
+            self.switch_mode(target_mode)
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let cntl2 = self
+                .read_register8::<regs::Cntl2>()
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let result = inner::verify_mode(target_mode, cntl2);
+            if result.is_err() {
+                self.inner.current_mode = None;
+            }
+            result
+        }
+        /// Does a single measurement and waits until the device is back in power-down mode.
+        ///
+        /// The device switches to power-down mode automatically after a single measurement, and
+        /// the mode register is polled until this is confirmed, so the host can safely go to sleep
+        /// afterwards. Returns [`Ak09916Error::Timeout`] if the device isn't in power-down mode
+        /// within `max_polls` polls of the mode register. The mode register is always polled at
+        /// least once.
+        pub async fn measure_and_power_down(
+            &mut self,
+            poll_us: u32,
+            max_polls: u32,
+        ) -> Result<Measurement, Ak09916Error<I::Error>> {
+            self.switch_mode(Mode::SingleMeasurement)
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let measurement = self
+                .poll_measurement(poll_us)
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let mut polls = 0;
+            loop {
+                let cntl2 = self
+                    .read_register8::<regs::Cntl2>()
+                    .await
+                    .map_err(Ak09916Error::I2c)?;
+                if cntl2.is_power_down() {
+                    break;
+                }
+                polls += 1;
+                if polls >= max_polls {
+                    return Err(Ak09916Error::Timeout);
+                }
+                self.inner.delay.delay_us(poll_us).await;
+            }
+            self.inner.set_mode(Some(Mode::PowerDown));
+            Ok(measurement)
+        }
+        /// Does a single measurement and returns the heading in degrees × 10, see
+        /// [`Measurement::heading_ned`].
+        ///
+        /// The device is confirmed to be back in power-down mode like in
+        /// [`measure_and_power_down`](Self::measure_and_power_down). Returns
+        /// [`Ak09916Error::ZeroField`] if the X and Y values are both zero.
+        pub async fn measure_and_compute_heading(
+            &mut self,
+            poll_us: u32,
+            max_polls: u32,
+        ) -> Result<i32, Ak09916Error<I::Error>> {
+            let measurement = self.measure_and_power_down(poll_us, max_polls).await?;
+            measurement.heading_ned().ok_or(Ak09916Error::ZeroField)
+        }
+        /// Does a full measurement using the given configuration.
+        ///
+        /// The device is switched to the configured mode and polled for measurement data, and
+        /// switched to power-down mode afterwards. Returns [`Ak09916Error::Timeout`] if no
+        /// measurement data was available within the configured number of polls.
+        pub async fn measure_with_config(
+            &mut self,
+            config: &MeasurementConfig<'_>,
+        ) -> Result<Measurement, Ak09916Error<I::Error>> {
+            self.switch_mode(config.mode)
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let mut polls = 0;
+            let measurement = loop {
+                if let Some(measurement) =
+                    self.read_measurement().await.map_err(Ak09916Error::I2c)?
+                {
+                    break Some(measurement);
+                }
+                polls += 1;
+                if polls >= config.max_polls {
+                    break None;
+                }
+                self.inner.delay.delay_us(config.poll_interval_us).await;
+            };
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            let measurement = measurement.ok_or(Ak09916Error::Timeout)?;
+            Ok(match config.apply_calibration {
+                Some(calibration) => calibration.apply(measurement),
+                None => measurement,
+            })
+        }
+        /// Performs a self-test.
+        ///
+        /// The device switches to power-down mode automatically after the operation.
+        pub async fn self_test(&mut self) -> Result<SelfTestResult, I::Error> {
+            self.self_test_with_tolerance(
+                SELF_TEST_TOLERANCE_X,
+                SELF_TEST_TOLERANCE_Y,
+                SELF_TEST_TOLERANCE_Z,
+            )
+            .await
+        }
+        /// Performs a self-test with custom per-axis tolerances.
+        ///
+        /// Each axis passes if its value is within the tolerance from the center of the datasheet
+        /// range, so e.g. `x_tol = 100` accepts X-axis values from -100 to 100. The datasheet
+        /// tolerances are [`SELF_TEST_TOLERANCE_X`] etc. The device switches to power-down mode
+        /// automatically after the operation.
+        pub async fn self_test_with_tolerance(
+            &mut self,
+            x_tol: i16,
+            y_tol: i16,
+            z_tol: i16,
+        ) -> Result<SelfTestResult, I::Error> {
+            self.switch_mode(Mode::SelfTest).await?;
+            let measurement = self.poll_measurement(10).await?;
+            Ok(SelfTestResult::with_tolerance(
+                measurement,
+                x_tol,
+                y_tol,
+                z_tol,
+            ))
+        }
+        /// Performs a self-test and also verifies that the device entered self-test mode.
+        ///
+        /// The mode register is read back right after the mode switch, and the test is only passed
+        /// if the device was in self-test mode. The device switches to power-down mode
+        /// automatically after the operation.
+        pub async fn self_test_verbose(&mut self) -> Result<SelfTestResult, I::Error> {
+            self.switch_mode(Mode::SelfTest).await?;
+            let cntl2 = self.read_register8::<regs::Cntl2>().await?;
+            let measurement = self.poll_measurement(10).await?;
+            Ok(SelfTestResult::with_mode_read_back(measurement, cntl2.0))
+        }
+        /// Consumes the driver and returns a moving average filter of measurements in the given
+        /// continuous mode.
+        ///
+        /// The device is switched to the given mode when the first measurement is requested.
+        /// The driver can be recovered with [`MeasurementMovingAvg::into_driver`].
+        pub fn measure_moving_average<const N: usize>(
+            self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> MeasurementMovingAvg<N, I, D> {
+            MeasurementMovingAvg {
+                driver: self,
+                mode,
+                poll_us,
+                started: false,
+                window: MeasurementQueue::new(),
+            }
+        }
+        /// Runs a built-in test sequence for e.g. manufacturing tests and field diagnostics.
+        ///
+        /// The sequence does a soft-reset, reads the Who I Am data, performs a self-test, collects
+        /// 10 measurements in continuous measurement mode 4 (100 Hz), and switches the device to
+        /// power-down mode. Failed checks are reported in the result, and only I²C errors abort
+        /// the sequence.
+        pub async fn run_built_in_self_test_sequence(
+            &mut self,
+        ) -> Result<BuiltInTestReport, Ak09916Error<I::Error>> {
+            self.soft_reset().await.map_err(Ak09916Error::I2c)?;
+            let who_i_am = self.who_i_am().await.map_err(Ak09916Error::I2c)?;
+            let self_test = self.self_test().await.map_err(Ak09916Error::I2c)?;
+            let samples = self
+                .continuous_read_n::<10>(
+                    Mode::Continuous100Hz,
+                    inner::poll_interval_for_mode(Mode::Continuous100Hz),
+                )
+                .await
+                .map_err(Ak09916Error::I2c)?;
+            Ok(BuiltInTestReport {
+                who_i_am,
+                who_i_am_valid: who_i_am == WhoIAm::AK09916,
+                self_test,
+                samples,
+                samples_valid: !samples.iter().any(Measurement::overflow),
+            })
+        }
+        /// Performs a soft-reset.
+        ///
+        /// The device switches to power-down mode automatically after the operation.
+        pub async fn soft_reset(&mut self) -> Result<(), I::Error> {
+            self.write_register8(regs::Cntl3::SRST).await?;
+            loop {
+                self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US).await;
+                let cntl3 = self.read_register8::<regs::Cntl3>().await?;
+                if !cntl3.contains(regs::Cntl3::SRST) {
+                    break Ok(());
+                }
+            }
+        }
+    }
+
+    // The driver is `Send` if the I²C bus and delay are `Send`
+    const _: () = {
+        fn assert_send<T: Send>() {}
+        #[allow(dead_code)]
+        fn assert_driver_send<I: I2c + Send, D: DelayNs + Send>() {
+            assert_send::<Ak09916<I, D>>();
+        }
+    };
+
+    impl<I: I2c, D: DelayNs> fmt::Debug for Ak09916<I, D> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.inner.fmt(f)
+        }
+    }
+
+    /// Low-level register access API
+    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
+        /// Reads an 8-bit register
+        pub async fn read_register8<R: Register8>(&mut self) -> Result<R, I::Error> {
+            let mut buffer = [0];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)
+                .await?;
+            Ok(R::from(buffer[0]))
+        }
+        /// Reads a 16-bit register
+        pub async fn read_register16<R: Register16>(&mut self) -> Result<R, I::Error> {
+            let mut buffer = [0, 0];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)
+                .await?;
+            Ok(R::from(i16::from_le_bytes(buffer)))
+        }
+        /// Reads the measurement data registers of all three axes in a single transaction.
+        ///
+        /// Unlike [`read_measurement`](Self::read_measurement), this doesn't read `ST1` or `ST2`, so
+        /// the device doesn't consider the data read finished and may not update the data registers
+        pub async fn read_all_axes_raw(
+            &mut self,
+        ) -> Result<(regs::Hx, regs::Hy, regs::Hz), I::Error> {
+            let mut buffer = [0; 6];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Hxl)], &mut buffer)
+                .await?;
+            Ok(regs::axes_from_raw_data(buffer))
+        }
+        /// Writes a 8-bit register
+        pub async fn write_register8<R: Register8>(&mut self, register: R) -> Result<(), I::Error> {
+            let buffer = inner::register8_write_buffer(register);
+            self.inner.before_write(buffer);
+            self.inner.i2c.write(I2C_ADDRESS, &buffer).await?;
+            self.inner.after_write(buffer);
+            Ok(())
+        }
+        /// Reads the raw `WIA1` and `WIA2` register data
+        pub async fn read_wia_raw(&mut self) -> Result<[u8; 2], I::Error> {
+            let mut buffer = [0; 2];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)
+                .await?;
+            Ok(buffer)
+        }
+        /// Reads the raw measurement register data from `ST1` to `ST2` in a single transaction.
+        ///
+        /// The data contains `ST1`, `HXL`..`HZH`, the dummy `TMPS` register, and `ST2`.
+        pub async fn read_measurement_raw(&mut self) -> Result<[u8; 9], I::Error> {
+            let mut buffer = [0; 9];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::St1)], &mut buffer)
+                .await?;
+            Ok(buffer)
+        }
+        /// Reads all readable registers, skipping the reserved and test registers.
+        ///
+        /// The registers are read in three transactions, one for each contiguous block, and byte
+        /// `i` of the result is the value of [`RegisterAddress::READABLE[i]`](RegisterAddress::READABLE).
+        pub async fn read_all_registers_burst(&mut self) -> Result<[u8; 14], I::Error> {
+            let mut buffer = [0; 14];
+            let (wia, rest) = buffer.split_at_mut(2);
+            let (measurement, cntl) = rest.split_at_mut(9);
+            for (address, block) in [
+                (RegisterAddress::Wia1, wia),
+                (RegisterAddress::St1, measurement),
+                (RegisterAddress::Cntl1, cntl),
+            ] {
+                self.inner
+                    .i2c
+                    .write_read(I2C_ADDRESS, &[u8::from(address)], block)
+                    .await?;
+            }
+            Ok(buffer)
+        }
+        /// Dumps all non-reserved register data
+        pub async fn dump_registers(&mut self) -> Result<regs::RegisterDump, I::Error> {
+            let mut buffer = [0; 16];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Wia1)], &mut buffer)
+                .await?;
+            Ok(regs::RegisterDump::from_raw_data(buffer))
+        }
+        /// Dumps all non-reserved register data and validates it.
+        ///
+        /// See [`RegisterDump::validate`](regs::RegisterDump::validate) for the checks.
+        pub async fn dump_and_validate(
+            &mut self,
+        ) -> Result<regs::RegisterDump, Ak09916Error<I::Error>> {
+            let dump = self.dump_registers().await.map_err(Ak09916Error::I2c)?;
+            dump.validate().map_err(Ak09916Error::InvalidRegisterDump)?;
+            Ok(dump)
+        }
+    }
+
+    /// Measurement data that is known to be ready
+    ///
+    /// Created by [`Ak09916::data_ready`].
+    pub struct MeasurementReady<'a, I: I2c, D: DelayNs> {
+        driver: &'a mut Ak09916<I, D>,
+        st1: regs::St1,
+    }
+
+    impl<I: I2c, D: DelayNs> MeasurementReady<'_, I, D> {
+        /// Reads the measurement data
+        pub async fn consume_measurement(self) -> Result<Measurement, I::Error> {
+            let mut buffer = [0; 8];
+            self.driver.inner.i2c.read(I2C_ADDRESS, &mut buffer).await?;
+            Ok(Measurement::from_raw_data(self.st1, buffer))
+        }
+    }
+
+    /// Moving average filter of continuous measurements that owns the driver
+    ///
+    /// Created by [`Ak09916::measure_moving_average`]. Each new measurement is averaged with the
+    /// previous ones, up to `N` measurements in total.
+    pub struct MeasurementMovingAvg<const N: usize, I: I2c, D: DelayNs> {
+        driver: Ak09916<I, D>,
+        mode: Mode,
+        poll_us: u32,
+        started: bool,
+        window: MeasurementQueue<N>,
+    }
+
+    impl<const N: usize, I: I2c, D: DelayNs> MeasurementMovingAvg<N, I, D> {
+        /// Polls a new measurement and returns the average of the last `N` measurements.
+        ///
+        /// The axes are rounded down, and the flags are the union of the flags of the averaged
+        /// measurements. If `N` is zero, the new measurement is returned as is.
+        pub async fn next_measurement(&mut self) -> Result<Measurement, I::Error> {
+            if !self.started {
+                self.driver.switch_mode(self.mode).await?;
+                self.started = true;
+            }
+            let measurement = self.driver.poll_measurement(self.poll_us).await?;
+            self.window.push_back_overwrite(measurement);
+            Ok(self.window.mean().unwrap_or(measurement))
+        }
+        /// Consumes the filter and returns the driver.
+        ///
+        /// The device is left in the continuous mode, if it was already switched to it.
+        pub fn into_driver(self) -> Ak09916<I, D> {
+            self.driver
+        }
+    }
+}
+
+/// Blocking API
+pub mod blocking {
+    use core::fmt;
+    use embedded_hal::{delay::DelayNs, digital::InputPin, i2c::I2c};
+
+    use crate::{
+        inner::{self, Ak09916Inner},
+        regs::{self, Register16, Register8, RegisterAddress},
+        Ak09916Error, BuiltInTestReport, Calibration, DriverState, MagSensor, Measurement,
+        MeasurementConfig, MeasurementFlags, MeasurementQueue, Mode, SelfTestResult, WhoIAm,
+        I2C_ADDRESS, MODE_POWER_ON_RESET_WAIT_TIME_US, MODE_SET_WAIT_TIME_US,
+        SELF_TEST_TOLERANCE_X, SELF_TEST_TOLERANCE_Y, SELF_TEST_TOLERANCE_Z,
+    };
+
+    /// AK09916 driver
+    ///
+    /// The driver can be cloned if the I²C bus and delay can be cloned. The clone is an
+    /// independent driver instance with a copy of the driver state, and whether the bus state is
+    /// shared depends on the bus type. Cloning doesn't add any locking.
+    #[derive(Clone)]
+    pub struct Ak09916<I: I2c, D: DelayNs> {
+        inner: Ak09916Inner<I, D>,
+    }
+
+    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
+        /// Creates a new blocking AK09916 driver
+        pub fn new(i2c: I, delay: D) -> Self {
+            Ak09916 {
+                inner: Ak09916Inner::new(i2c, delay),
+            }
+        }
+        /// Creates a new blocking AK09916 driver with the given stored calibration.
+        ///
+        /// This is the inverse of [`into_parts`](Self::into_parts). The calibration is not applied
+        /// to measurements automatically.
+        pub fn from_parts(i2c: I, delay: D, calibration: Option<Calibration>) -> Self {
+            Ak09916 {
+                inner: Ak09916Inner::from_parts(i2c, delay, calibration),
+            }
+        }
+        /// Consumes the driver and returns the I²C bus and delay used by it
+        pub fn into_inner(self) -> (I, D) {
+            self.inner.release()
+        }
+        /// Consumes the driver and releases resources used by it
+        #[deprecated(note = "use `into_inner` instead")]
+        pub fn release(self) -> (I, D) {
+            self.into_inner()
+        }
+        /// Consumes the driver and returns the resources used by it and the stored calibration
+        pub fn into_parts(self) -> (I, D, Option<Calibration>) {
+            self.inner.into_parts()
+        }
+        /// Returns a recommended [`poll_measurement`](Self::poll_measurement) interval in μs for
+        /// the given mode.
+        ///
+        /// This is a tenth of the measurement period for continuous measurement modes, and 1 ms
+        /// for other modes.
+        pub fn poll_interval_for_mode(mode: Mode) -> u32 {
+            inner::poll_interval_for_mode(mode)
+        }
+        /// Returns the last mode written to the device, without doing any I²C communication.
+        ///
+        /// Returns None if the mode is unknown, e.g. before the first mode switch or after a
+        /// soft-reset. Single measurement and self-test modes are never returned, because the
+        /// device switches to power-down mode automatically after the measurement. Writes done
+        /// through [`borrow_i2c_mut`](Self::borrow_i2c_mut) are not tracked.
+        pub fn current_mode(&self) -> Option<Mode> {
+            self.inner.current_mode
+        }
+        /// Replaces the calibration stored in the driver
+        pub fn set_calibration(&mut self, calibration: Option<Calibration>) {
+            self.inner.calibration = calibration;
+        }
+        /// Returns the calibration stored in the driver, if any
+        pub fn get_calibration(&self) -> Option<&Calibration> {
+            self.inner.calibration.as_ref()
+        }
+        /// Returns a snapshot of the driver state.
+        ///
+        /// No I²C communication is done, so the mode is None if it's unknown.
+        pub fn save_state(&self) -> DriverState {
+            DriverState {
+                mode: self.inner.current_mode,
+                calibration: self.inner.calibration,
+            }
+        }
+        /// Restores a driver state snapshot, switching the device to the saved mode if it's known.
+        ///
+        /// The device may have been power cycled since the snapshot was taken, so the cached mode
+        /// is cleared and the saved mode is always written, even if the cached mode matches it.
+        pub fn restore_state(&mut self, state: DriverState) -> Result<(), I::Error> {
+            self.inner.calibration = state.calibration;
+            self.inner.current_mode = None;
+            match state.mode {
+                Some(mode) => self.switch_mode(mode),
+                None => Ok(()),
+            }
+        }
+        /// Sets a callback that is called with measurements that have the overflow flag set.
+        ///
+        /// The callback is called by [`poll_measurement`](Self::poll_measurement) and
+        /// [`read_measurement`](Self::read_measurement) before returning the measurement, so it's
+        /// also called by the methods and continuous iterators built on them. Reads that bypass
+        /// them don't call it: [`read_measurement_unchecked`](Self::read_measurement_unchecked),
+        /// [`read_into_buffer`](Self::read_into_buffer), reads triggered by the DRDY pin in
+        /// [`poll_measurement_nb`](Self::poll_measurement_nb), and
+        /// [`MeasurementReady::consume_measurement`].
+        ///
+        /// The callback is a plain function pointer, so it can't capture any state. Use e.g. a
+        /// static atomic to pass information out of it. Pass None to remove the callback.
+        pub fn on_overflow(&mut self, callback: Option<fn(&Measurement)>) {
+            self.inner.overflow_callback = callback;
+        }
+        /// Sets the poll interval in μs for [`poll_measurement_auto`](Self::poll_measurement_auto)
+        pub fn set_poll_interval(&mut self, poll_us: u32) {
+            self.inner.poll_interval_us = Some(poll_us);
+        }
+        /// Returns the poll interval in μs used by
+        /// [`poll_measurement_auto`](Self::poll_measurement_auto).
+        ///
+        /// If no interval has been set, this is the recommended interval for the current mode
+        /// according to [`poll_interval_for_mode`](Self::poll_interval_for_mode).
+        pub fn get_poll_interval(&self) -> u32 {
+            self.inner.poll_interval_us()
+        }
+        /// Returns a mutable reference to the I²C bus for temporary use by other code.
+        ///
+        /// Any transactions using the bus must be finished before any driver method is called again.
+        pub fn borrow_i2c_mut(&mut self) -> &mut I {
+            &mut self.inner.i2c
+        }
+        /// Checks if the device acknowledges its address by doing a zero-length write.
+        ///
+        /// Returns false if the device doesn't acknowledge, and an error for other I²C errors
+        pub fn is_connected(&mut self) -> Result<bool, I::Error> {
+            match self.inner.i2c.write(I2C_ADDRESS, &[]) {
+                Ok(()) => Ok(true),
+                Err(err) if inner::is_nack(&err) => Ok(false),
+                Err(err) => Err(err),
+            }
+        }
+        /// Reads the Who I Am information from the device
+        pub fn who_i_am(&mut self) -> Result<WhoIAm, I::Error> {
+            let buffer = self.read_wia_raw()?;
+            Ok(inner::who_i_am_from_raw_data(buffer))
+        }
+        /// Waits for the device to become ready for I²C communication, e.g. after power-up.
+        ///
+        /// The driver waits [`MODE_POWER_ON_RESET_WAIT_TIME_US`] first, and then the device IDs are
+        /// read. The read is retried up to `retry_count` times with `retry_delay_us` μs between
+        /// attempts. Returns [`Ak09916Error::DeviceNotFound`] if all attempts fail.
+        pub fn wait_for_device(
+            &mut self,
+            retry_count: u32,
+            retry_delay_us: u32,
+        ) -> Result<WhoIAm, Ak09916Error<I::Error>> {
+            self.inner.delay.delay_us(MODE_POWER_ON_RESET_WAIT_TIME_US);
+            self.who_i_am_retry(retry_count.saturating_add(1), retry_delay_us)
+                .map_err(|_| Ak09916Error::DeviceNotFound)
+        }
+        /// Reads the device IDs, retrying on I²C errors, and checks that the device is AK09916.
+        ///
+        /// The read is attempted up to `max_attempts` times (at least once) with `delay_us` μs
+        /// between attempts. Returns the I²C error of the last attempt if all attempts fail, or
+        /// [`Ak09916Error::WrongDevice`] if the device IDs don't match.
+        pub fn read_who_i_am_loop(
+            &mut self,
+            max_attempts: u32,
+            delay_us: u32,
+        ) -> Result<WhoIAm, Ak09916Error<I::Error>> {
+            let who_i_am = self
+                .who_i_am_retry(max_attempts, delay_us)
+                .map_err(Ak09916Error::I2c)?;
+            if who_i_am == WhoIAm::AK09916 {
+                Ok(who_i_am)
+            } else {
+                Err(Ak09916Error::WrongDevice(who_i_am))
+            }
+        }
+        /// Reads the device IDs up to `max_attempts` times (at least once) with `delay_us` μs
+        /// between attempts, returning the I²C error of the last attempt if all attempts fail
+        fn who_i_am_retry(&mut self, max_attempts: u32, delay_us: u32) -> Result<WhoIAm, I::Error> {
+            let mut attempts = 1;
+            loop {
+                match self.who_i_am() {
+                    Ok(who_i_am) => break Ok(who_i_am),
+                    Err(err) if attempts >= max_attempts => break Err(err),
+                    Err(_) => {
+                        attempts += 1;
+                        self.inner.delay.delay_us(delay_us);
+                    }
+                }
+            }
+        }
+        /// Polls the device for measurement data until it's available
+        pub fn poll_measurement(&mut self, poll_interval_us: u32) -> Result<Measurement, I::Error> {
+            let mut st1: regs::St1;
+            loop {
+                st1 = self.read_register8::<regs::St1>()?;
+                if st1.data_ready() {
+                    break;
+                }
+                self.inner.delay.delay_us(poll_interval_us);
+            }
+            let mut buffer = [0; 8];
+            self.inner.i2c.read(I2C_ADDRESS, &mut buffer)?;
+            Ok(self
+                .inner
+                .check_overflow(Measurement::from_raw_data(st1, buffer)))
+        }
+        /// Polls the device for measurement data until it's available, using the poll interval
+        /// returned by [`get_poll_interval`](Self::get_poll_interval)
+        pub fn poll_measurement_auto(&mut self) -> Result<Measurement, I::Error> {
+            let poll_interval_us = self.get_poll_interval();
+            self.poll_measurement(poll_interval_us)
+        }
+        /// Reads the latest measurement data, if available.
+        ///
+        /// Returns None if measurement data is not ready
+        pub fn read_measurement(&mut self) -> Result<Option<Measurement>, I::Error> {
+            let st1 = self.read_register8::<regs::St1>()?;
+            if st1.data_ready() {
+                let mut buffer = [0; 8];
+                self.inner.i2c.read(I2C_ADDRESS, &mut buffer)?;
+                let measurement = Measurement::from_raw_data(st1, buffer);
+                Ok(Some(self.inner.check_overflow(measurement)))
+            } else {
+                Ok(None)
+            }
+        }
+        /// Reads the latest measurement data if the DRDY pin signals that it's available.
+        ///
+        /// Returns None without any I²C transactions if the pin is low, which saves bus bandwidth
+        /// compared to polling `ST1`. If the pin can't be read, `ST1` is checked instead.
+        pub fn poll_measurement_nb<P: InputPin>(
+            &mut self,
+            drdy_pin: &mut P,
+        ) -> Result<Option<Measurement>, I::Error> {
+            match drdy_pin.is_high() {
+                Ok(false) => Ok(None),
+                Ok(true) => {
+                    let [st1, data @ ..] = self.read_measurement_raw()?;
+                    Ok(Some(Measurement::from_raw_data(regs::St1::from(st1), data)))
+                }
+                Err(_) => self.read_measurement(),
+            }
+        }
+        /// Reads the latest measurement data, or returns the previous one if new data is not ready.
+        ///
+        /// A previous measurement is returned with the overrun flag set. If this method hasn't
+        /// returned fresh data before, the data registers are read without checking if they're
+        /// ready, and the overrun flag is set.
+        pub fn read_measurement_or_stale(&mut self) -> Result<Measurement, I::Error> {
+            if let Some(measurement) = self.read_measurement()? {
+                self.inner.last_measurement = Some(measurement);
+                return Ok(measurement);
+            }
+            let mut measurement = match self.inner.last_measurement {
+                Some(measurement) => measurement,
+                None => self.read_measurement_unchecked()?,
+            };
+            measurement.flags.insert(MeasurementFlags::OVERRUN);
+            Ok(measurement)
+        }
+        /// Checks if measurement data is ready, returning a handle for reading it.
+        ///
+        /// The handle borrows the driver, so no other transactions can happen before the
+        /// measurement is read, and reading it never returns None.
+        pub fn data_ready(&mut self) -> Result<Option<MeasurementReady<'_, I, D>>, I::Error> {
+            let st1 = self.read_register8::<regs::St1>()?;
+            Ok(if st1.data_ready() {
+                Some(MeasurementReady { driver: self, st1 })
+            } else {
+                None
+            })
+        }
+        /// Reads the latest measurement data, retrying up to `max_retries` times if it's not ready.
+        ///
+        /// The driver waits `poll_us` μs before each retry. Returns None if measurement data was
+        /// not ready after all retries.
+        pub fn read_measurement_retry(
+            &mut self,
+            max_retries: u32,
+            poll_us: u32,
+        ) -> Result<Option<Measurement>, I::Error> {
+            let mut retries = 0;
+            loop {
+                if let Some(measurement) = self.read_measurement()? {
+                    break Ok(Some(measurement));
+                }
+                if retries >= max_retries {
+                    break Ok(None);
+                }
+                retries += 1;
+                self.inner.delay.delay_us(poll_us);
+            }
+        }
+        /// Reads measurement data without checking if it's ready.
+        ///
+        /// This skips reading `ST1`, so the measurement is read in a single transaction, but the data
+        /// may be stale and the data overrun flag is never set. This is only safe if the host
+        /// guarantees that new data is available, e.g. by reading at a slower rate than the
+        /// continuous measurement mode frequency. The [`on_overflow`](Self::on_overflow) callback
+        /// isn't called.
+        pub fn read_measurement_unchecked(&mut self) -> Result<Measurement, I::Error> {
+            let mut buffer = [0; 8];
+            self.read_into_buffer(&mut buffer)
+        }
+        /// Reads measurement data without checking if it's ready, using a caller-owned buffer.
+        ///
+        /// This is the same as [`read_measurement_unchecked`](Self::read_measurement_unchecked),
+        /// but the buffer is passed directly to the I²C bus, which is useful for e.g. DMA-backed
+        /// I²C implementations. Afterwards the buffer contains the raw `HXL`..`HZH`, `TMPS`, and
+        /// `ST2` register data. The [`on_overflow`](Self::on_overflow) callback isn't called.
+        pub fn read_into_buffer(&mut self, buf: &mut [u8; 8]) -> Result<Measurement, I::Error> {
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(RegisterAddress::Hxl)], buf)?;
+            Ok(Measurement::from_raw_data(regs::St1::empty(), *buf))
+        }
+        /// Polls the device for measurement data until it's available or the time budget runs out.
+        ///
+        /// The elapsed time is estimated from the poll delays, so time spent in I²C transactions is
+        /// not included. Returns None if no measurement data was available within `deadline_us` μs.
+        pub fn read_measurement_blocking_until(
+            &mut self,
+            deadline_us: u32,
+            poll_interval_us: u32,
+        ) -> Result<Option<Measurement>, I::Error> {
+            let mut elapsed_us = 0;
+            loop {
+                if let Some(measurement) = self.read_measurement()? {
+                    break Ok(Some(measurement));
+                }
+                match inner::next_poll_delay(elapsed_us, deadline_us, poll_interval_us) {
+                    Some(delay_us) => {
+                        self.inner.delay.delay_us(delay_us);
+                        elapsed_us += delay_us;
+                    }
+                    None => break Ok(None),
+                }
+            }
+        }
+        /// Switches the device to the given mode and collects exactly `N` measurements.
+        ///
+        /// The device is left in the given mode.
+        pub fn fill_queue<const N: usize>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> Result<MeasurementQueue<N>, I::Error> {
+            self.switch_mode(mode)?;
+            let mut queue = MeasurementQueue::new();
+            while !queue.is_full() {
+                let measurement = self.poll_measurement(poll_us)?;
+                let _ = queue.push_back(measurement);
+            }
+            Ok(queue)
+        }
+        /// Switches the device to the given mode, collects exactly `N` measurements, and switches
+        /// the device to power-down mode.
+        pub fn continuous_read_n<const N: usize>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> Result<[Measurement; N], I::Error> {
+            self.switch_mode(mode)?;
+            let mut measurements = [Measurement::ZERO; N];
+            for measurement in measurements.iter_mut() {
+                *measurement = self.poll_measurement(poll_us)?;
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))?;
+            Ok(measurements)
+        }
+        /// Switches the device to the given mode and passes each measurement to `f` until it
+        /// returns false.
+        ///
+        /// The device is switched to power-down mode before returning, also if reading a
+        /// measurement fails.
+        pub fn continuous_poll_fn<F: FnMut(Measurement) -> bool>(
+            &mut self,
+            mode: Mode,
+            poll_us: u32,
+            mut f: F,
+        ) -> Result<(), I::Error> {
+            self.switch_mode(mode)?;
+            let result = loop {
+                match self.poll_measurement(poll_us) {
+                    Ok(measurement) if f(measurement) => (),
+                    Ok(_) => break Ok(()),
+                    Err(err) => break Err(err),
+                }
+            };
+            let power_down = self.write_register8(regs::Cntl2::from(Mode::PowerDown));
+            result.and(power_down)
+        }
+        /// Does `warmup_count` single measurements, discarding the results, and then switches the
+        /// device to the given continuous measurement mode.
+        ///
+        /// The device is switched to power-down mode with the required wait time before every
+        /// mode switch.
+        pub fn single_then_continuous(
+            &mut self,
+            warmup_count: u32,
+            continuous_mode: Mode,
+        ) -> Result<(), I::Error> {
+            for _ in 0..warmup_count {
+                self.switch_mode(Mode::SingleMeasurement)?;
+                self.poll_measurement(10)?;
+            }
+            self.switch_mode(continuous_mode)
+        }
+        /// Switches the device to the given mode.
+        ///
+        /// Nothing is written if the device is known to be in the given mode already.
+        pub fn switch_mode(&mut self, target_mode: Mode) -> Result<(), I::Error> {
+            if self.inner.current_mode == Some(target_mode) {
+                return Ok(());
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))?;
+            self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US);
+            self.write_register8(regs::Cntl2::from(target_mode))
+        }
+        /// Switches the device to the given mode, skipping the power-down step if possible.
+        ///
+        /// The mode register is read first unless the current mode is known, and the device is only
+        /// switched to power-down mode if it's not already in power-down mode. The required wait
+        /// time is always observed before the new mode is written, because the device may have
+        /// been switched to power-down mode just before.
+        pub fn reconfigure_mode(&mut self, new_mode: Mode) -> Result<(), I::Error> {
+            if new_mode == Mode::PowerDown {
+                return self.power_down();
+            }
+            if self.inner.current_mode == Some(new_mode) {
+                return Ok(());
+            }
+            let powered_down = match self.inner.current_mode {
+                Some(mode) => mode == Mode::PowerDown,
+                None => self.read_register8::<regs::Cntl2>()?.is_power_down(),
+            };
+            if !powered_down {
+                self.write_register8(regs::Cntl2::from(Mode::PowerDown))?;
+            }
+            self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US);
+            self.write_register8(regs::Cntl2::from(new_mode))
+        }
+        /// Switches the device to power-down mode.
+        ///
+        /// The mode register is read first unless the current mode is known, and nothing is written
+        /// if the device is already in power-down mode.
+        pub fn power_down(&mut self) -> Result<(), I::Error> {
+            if self.inner.current_mode == Some(Mode::PowerDown) {
+                return Ok(());
+            }
+            let cntl2 = self.read_register8::<regs::Cntl2>()?;
+            if cntl2.is_power_down() {
+                self.inner.set_mode(Some(Mode::PowerDown));
+                return Ok(());
+            }
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+        }
+        /// Switches the device to the given mode and verifies it by reading back the mode register.
+        ///
+        /// Note that single measurement and self-test modes switch to power-down mode automatically
+        /// after the measurement, so the read back mode may not match if the measurement is
+        /// already finished.
+        pub fn checked_switch_mode(
+            &mut self,
+            target_mode: Mode,
+        ) -> Result<(), Ak09916Error<I::Error>> {
+            self.switch_mode(target_mode).map_err(Ak09916Error::I2c)?;
+            let cntl2 = self
+                .read_register8::<regs::Cntl2>()
+                .map_err(Ak09916Error::I2c)?;
+            let result = inner::verify_mode(target_mode, cntl2);
+            if result.is_err() {
+                self.inner.current_mode = None;
+            }
+            result
+        }
+        /// Does a single measurement and waits until the device is back in power-down mode.
+        ///
+        /// The device switches to power-down mode automatically after a single measurement, and
+        /// the mode register is polled until this is confirmed, so the host can safely go to sleep
+        /// afterwards. Returns [`Ak09916Error::Timeout`] if the device isn't in power-down mode
+        /// within `max_polls` polls of the mode register. The mode register is always polled at
+        /// least once.
+        pub fn measure_and_power_down(
+            &mut self,
+            poll_us: u32,
+            max_polls: u32,
+        ) -> Result<Measurement, Ak09916Error<I::Error>> {
+            self.switch_mode(Mode::SingleMeasurement)
+                .map_err(Ak09916Error::I2c)?;
+            let measurement = self.poll_measurement(poll_us).map_err(Ak09916Error::I2c)?;
+            let mut polls = 0;
+            loop {
+                let cntl2 = self
+                    .read_register8::<regs::Cntl2>()
+                    .map_err(Ak09916Error::I2c)?;
+                if cntl2.is_power_down() {
+                    break;
+                }
+                polls += 1;
+                if polls >= max_polls {
+                    return Err(Ak09916Error::Timeout);
+                }
+                self.inner.delay.delay_us(poll_us);
+            }
+            self.inner.set_mode(Some(Mode::PowerDown));
+            Ok(measurement)
+        }
+        /// Does a single measurement and returns the heading in degrees × 10, see
+        /// [`Measurement::heading_ned`].
+        ///
+        /// The device is confirmed to be back in power-down mode like in
+        /// [`measure_and_power_down`](Self::measure_and_power_down). Returns
+        /// [`Ak09916Error::ZeroField`] if the X and Y values are both zero.
+        pub fn measure_and_compute_heading(
+            &mut self,
+            poll_us: u32,
+            max_polls: u32,
+        ) -> Result<i32, Ak09916Error<I::Error>> {
+            let measurement = self.measure_and_power_down(poll_us, max_polls)?;
+            measurement.heading_ned().ok_or(Ak09916Error::ZeroField)
+        }
+        /// Does a full measurement using the given configuration.
+        ///
+        /// The device is switched to the configured mode and polled for measurement data, and
+        /// switched to power-down mode afterwards. Returns [`Ak09916Error::Timeout`] if no
+        /// measurement data was available within the configured number of polls.
+        pub fn measure_with_config(
+            &mut self,
+            config: &MeasurementConfig<'_>,
+        ) -> Result<Measurement, Ak09916Error<I::Error>> {
+            self.switch_mode(config.mode).map_err(Ak09916Error::I2c)?;
+            let mut polls = 0;
+            let measurement = loop {
+                if let Some(measurement) = self.read_measurement().map_err(Ak09916Error::I2c)? {
+                    break Some(measurement);
+                }
+                polls += 1;
+                if polls >= config.max_polls {
+                    break None;
+                }
+                self.inner.delay.delay_us(config.poll_interval_us);
+            };
+            self.write_register8(regs::Cntl2::from(Mode::PowerDown))
+                .map_err(Ak09916Error::I2c)?;
+            let measurement = measurement.ok_or(Ak09916Error::Timeout)?;
+            Ok(match config.apply_calibration {
+                Some(calibration) => calibration.apply(measurement),
+                None => measurement,
+            })
+        }
+        /// Performs a self-test.
+        ///
+        /// The device switches to power-down mode automatically after the operation.
+        pub fn self_test(&mut self) -> Result<SelfTestResult, I::Error> {
+            self.self_test_with_tolerance(
+                SELF_TEST_TOLERANCE_X,
+                SELF_TEST_TOLERANCE_Y,
+                SELF_TEST_TOLERANCE_Z,
+            )
+        }
+        /// Performs a self-test with custom per-axis tolerances.
+        ///
+        /// Each axis passes if its value is within the tolerance from the center of the datasheet
+        /// range, so e.g. `x_tol = 100` accepts X-axis values from -100 to 100. The datasheet
+        /// tolerances are [`SELF_TEST_TOLERANCE_X`] etc. The device switches to power-down mode
+        /// automatically after the operation.
+        pub fn self_test_with_tolerance(
+            &mut self,
+            x_tol: i16,
+            y_tol: i16,
+            z_tol: i16,
+        ) -> Result<SelfTestResult, I::Error> {
+            self.switch_mode(Mode::SelfTest)?;
+            let measurement = self.poll_measurement(10)?;
+            Ok(SelfTestResult::with_tolerance(
+                measurement,
+                x_tol,
+                y_tol,
+                z_tol,
+            ))
+        }
+        /// Performs a self-test and also verifies that the device entered self-test mode.
+        ///
+        /// The mode register is read back right after the mode switch, and the test is only passed
+        /// if the device was in self-test mode. The device switches to power-down mode
+        /// automatically after the operation.
+        pub fn self_test_verbose(&mut self) -> Result<SelfTestResult, I::Error> {
+            self.switch_mode(Mode::SelfTest)?;
+            let cntl2 = self.read_register8::<regs::Cntl2>()?;
+            let measurement = self.poll_measurement(10)?;
+            Ok(SelfTestResult::with_mode_read_back(measurement, cntl2.0))
+        }
+        /// Runs a built-in test sequence for e.g. manufacturing tests and field diagnostics.
+        ///
+        /// The sequence does a soft-reset, reads the Who I Am data, performs a self-test, collects
+        /// 10 measurements in continuous measurement mode 4 (100 Hz), and switches the device to
+        /// power-down mode. Failed checks are reported in the result, and only I²C errors abort
+        /// the sequence.
+        pub fn run_built_in_self_test_sequence(
+            &mut self,
+        ) -> Result<BuiltInTestReport, Ak09916Error<I::Error>> {
+            self.soft_reset().map_err(Ak09916Error::I2c)?;
+            let who_i_am = self.who_i_am().map_err(Ak09916Error::I2c)?;
+            let self_test = self.self_test().map_err(Ak09916Error::I2c)?;
+            let samples = self
+                .continuous_read_n::<10>(
+                    Mode::Continuous100Hz,
+                    inner::poll_interval_for_mode(Mode::Continuous100Hz),
+                )
+                .map_err(Ak09916Error::I2c)?;
+            Ok(BuiltInTestReport {
+                who_i_am,
+                who_i_am_valid: who_i_am == WhoIAm::AK09916,
+                self_test,
+                samples,
+                samples_valid: !samples.iter().any(Measurement::overflow),
+            })
+        }
+        /// Performs a soft-reset.
+        ///
+        /// The device switches to power-down mode automatically after the operation.
+        pub fn soft_reset(&mut self) -> Result<(), I::Error> {
+            self.write_register8(regs::Cntl3::SRST)?;
+            loop {
+                self.inner.delay.delay_us(MODE_SET_WAIT_TIME_US);
+                let cntl3 = self.read_register8::<regs::Cntl3>()?;
+                if !cntl3.contains(regs::Cntl3::SRST) {
+                    break Ok(());
+                }
+            }
+        }
+        /// Consumes the driver and returns an iterator of measurements in the given continuous mode.
+        ///
+        /// The device is switched to the given mode when the first measurement is requested.
+        /// The driver can be recovered with [`ContinuousIter::into_driver`].
+        pub fn into_continuous_iter(self, mode: Mode, poll_us: u32) -> ContinuousIter<I, D> {
+            ContinuousIter {
+                driver: self,
+                mode,
+                poll_us,
+                started: false,
+            }
+        }
+        /// Consumes the driver and returns a moving average filter of measurements in the given
+        /// continuous mode.
+        ///
+        /// The device is switched to the given mode when the first measurement is requested.
+        /// The driver can be recovered with [`MeasurementMovingAvg::into_driver`].
+        pub fn measure_moving_average<const N: usize>(
+            self,
+            mode: Mode,
+            poll_us: u32,
+        ) -> MeasurementMovingAvg<N, I, D> {
+            MeasurementMovingAvg {
+                driver: self,
+                mode,
+                poll_us,
+                started: false,
+                window: MeasurementQueue::new(),
+            }
+        }
+        /// Runs the given operation, retrying it up to `max_retries` times if it fails.
+        ///
+        /// The driver waits `retry_delay_us` μs before each retry. If all attempts fail, the error of
+        /// the last attempt is returned.
+        ///
+        /// This is only useful for transient errors (e.g. bit errors on a noisy bus), and is not
+        /// appropriate for errors that require re-initializing the device.
+        pub fn with_retry<F, R>(
+            &mut self,
+            max_retries: u32,
+            retry_delay_us: u32,
+            mut op: F,
+        ) -> Result<R, I::Error>
+        where
+            F: FnMut(&mut Self) -> Result<R, I::Error>,
+        {
+            let mut retries = 0;
+            loop {
+                match op(self) {
+                    Ok(result) => break Ok(result),
+                    Err(err) if retries >= max_retries => break Err(err),
+                    Err(_) => {
+                        retries += 1;
+                        self.inner.delay.delay_us(retry_delay_us);
+                    }
+                }
+            }
+        }
+    }
+
+    impl<I, D> Ak09916<I, D>
+    where
+        I: I2c + embedded_hal_async::i2c::I2c,
+        D: DelayNs,
+    {
+        /// Converts the driver to an asynchronous driver that uses the given delay.
+        ///
+        /// The current delay is dropped, and the stored calibration is preserved.
+        pub fn into_async_with_delay<D2: embedded_hal_async::delay::DelayNs>(
+            self,
+            delay: D2,
+        ) -> crate::asynch::Ak09916<I, D2> {
+            let (i2c, _, calibration) = self.into_parts();
+            crate::asynch::Ak09916::from_parts(i2c, delay, calibration)
+        }
+    }
+
+    // The driver is `Send` if the I²C bus and delay are `Send`
+    const _: () = {
+        fn assert_send<T: Send>() {}
+        #[allow(dead_code)]
+        fn assert_driver_send<I: I2c + Send, D: DelayNs + Send>() {
+            assert_send::<Ak09916<I, D>>();
+        }
+    };
+
+    impl<I: I2c, D: DelayNs> fmt::Debug for Ak09916<I, D> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.inner.fmt(f)
+        }
+    }
+
+    impl<I: I2c, D: DelayNs> MagSensor for Ak09916<I, D> {
+        type Error = I::Error;
+
+        fn read_measurement(&mut self) -> Result<Option<Measurement>, Self::Error> {
+            Ak09916::read_measurement(self)
+        }
+        fn switch_mode(&mut self, mode: Mode) -> Result<(), Self::Error> {
+            Ak09916::switch_mode(self, mode)
+        }
+    }
+
+    /// Low-level register access API
+    impl<I: I2c, D: DelayNs> Ak09916<I, D> {
+        /// Reads an 8-bit register
+        pub fn read_register8<R: Register8>(&mut self) -> Result<R, I::Error> {
+            let mut buffer = [0];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)?;
+            Ok(R::from(buffer[0]))
+        }
+        /// Reads a 16-bit register
+        pub fn read_register16<R: Register16>(&mut self) -> Result<R, I::Error> {
+            let mut buffer = [0, 0];
+            self.inner
+                .i2c
+                .write_read(I2C_ADDRESS, &[u8::from(R::ADDRESS)], &mut buffer)?;
+            Ok(R::from(i16::from_le_bytes(buffer)))
+        }
+        /// Reads the measurement data registers of all three axes in a single transaction.
+        ///
+        /// Unlike [`read_measurement`](Self::read_measurement), this doesn't read `ST1` or `ST2`, so
+        /// the device doesn't consider the data read finished and may not update the data registers
+        pub fn read_all_axes_raw(&mut self) -> Result<(regs::Hx, regs::Hy, regs::Hz), I::Error> {
+            let mut buffer = [0; 6];
+            self.inner.i2c.write_read(
+                I2C_ADDRESS,
+                &[u8::from(RegisterAddress::Hxl)],
+                &mut buffer,
+            )?;
+            Ok(regs::axes_from_raw_data(buffer))
+        }
+        /// Writes a 8-bit register
+        pub fn write_register8<R: Register8>(&mut self, register: R) -> Result<(), I::Error> {
+            let buffer = inner::register8_write_buffer(register);
+            self.inner.before_write(buffer);
+            self.inner.i2c.write(I2C_ADDRESS, &buffer)?;
+            self.inner.after_write(buffer);
+            Ok(())
+        }
+        /// Reads the raw `WIA1` and `WIA2` register data
+        pub fn read_wia_raw(&mut self) -> Result<[u8; 2], I::Error> {
+            let mut buffer = [0; 2];
+            self.inner.i2c.write_read(
+                I2C_ADDRESS,
+                &[u8::from(RegisterAddress::Wia1)],
+                &mut buffer,
+            )?;
+            Ok(buffer)
+        }
+        /// Reads the raw measurement register data from `ST1` to `ST2` in a single transaction.
+        ///
+        /// The data contains `ST1`, `HXL`..`HZH`, the dummy `TMPS` register, and `ST2`.
+        pub fn read_measurement_raw(&mut self) -> Result<[u8; 9], I::Error> {
+            let mut buffer = [0; 9];
+            self.inner.i2c.write_read(
+                I2C_ADDRESS,
+                &[u8::from(RegisterAddress::St1)],
+                &mut buffer,
+            )?;
+            Ok(buffer)
+        }
+        /// Reads all readable registers, skipping the reserved and test registers.
+        ///
+        /// The registers are read in three transactions, one for each contiguous block, and byte
+        /// `i` of the result is the value of [`RegisterAddress::READABLE[i]`](RegisterAddress::READABLE).
+        pub fn read_all_registers_burst(&mut self) -> Result<[u8; 14], I::Error> {
+            let mut buffer = [0; 14];
+            let (wia, rest) = buffer.split_at_mut(2);
+            let (measurement, cntl) = rest.split_at_mut(9);
+            for (address, block) in [
+                (RegisterAddress::Wia1, wia),
+                (RegisterAddress::St1, measurement),
+                (RegisterAddress::Cntl1, cntl),
+            ] {
+                self.inner
+                    .i2c
+                    .write_read(I2C_ADDRESS, &[u8::from(address)], block)?;
+            }
+            Ok(buffer)
+        }
+        /// Dumps all non-reserved register data
+        pub fn dump_registers(&mut self) -> Result<regs::RegisterDump, I::Error> {
+            let mut buffer = [0; 16];
+            self.inner.i2c.write_read(
+                I2C_ADDRESS,
+                &[u8::from(RegisterAddress::Wia1)],
+                &mut buffer,
+            )?;
+            Ok(regs::RegisterDump::from_raw_data(buffer))
+        }
+        /// Dumps all non-reserved register data and validates it.
+        ///
+        /// See [`RegisterDump::validate`](regs::RegisterDump::validate) for the checks.
+        pub fn dump_and_validate(&mut self) -> Result<regs::RegisterDump, Ak09916Error<I::Error>> {
+            let dump = self.dump_registers().map_err(Ak09916Error::I2c)?;
+            dump.validate().map_err(Ak09916Error::InvalidRegisterDump)?;
+            Ok(dump)
+        }
+    }
+
+    /// Iterator of continuous measurements that owns the driver
+    ///
+    /// Created by [`Ak09916::into_continuous_iter`]. The iterator never ends by itself, and each item
+    /// is the result of [`Ak09916::poll_measurement`].
+    pub struct ContinuousIter<I: I2c, D: DelayNs> {
+        driver: Ak09916<I, D>,
+        mode: Mode,
+        poll_us: u32,
+        started: bool,
+    }
+
+    impl<I: I2c, D: DelayNs> ContinuousIter<I, D> {
+        /// Consumes the iterator and returns the driver.
+        ///
+        /// The device is left in the continuous mode, if it was already switched to it.
+        pub fn into_driver(self) -> Ak09916<I, D> {
+            self.driver
+        }
+    }
+
+    impl<I: I2c, D: DelayNs> Iterator for ContinuousIter<I, D> {
+        type Item = Result<Measurement, I::Error>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if !self.started {
+                if let Err(err) = self.driver.switch_mode(self.mode) {
+                    return Some(Err(err));
+                }
+                self.started = true;
+            }
+            Some(self.driver.poll_measurement(self.poll_us))
+        }
+    }
+
+    /// Measurement data that is known to be ready
+    ///
+    /// Created by [`Ak09916::data_ready`].
+    pub struct MeasurementReady<'a, I: I2c, D: DelayNs> {
+        driver: &'a mut Ak09916<I, D>,
+        st1: regs::St1,
+    }
+
+    impl<I: I2c, D: DelayNs> MeasurementReady<'_, I, D> {
+        /// Reads the measurement data
+        pub fn consume_measurement(self) -> Result<Measurement, I::Error> {
+            let mut buffer = [0; 8];
+            self.driver.inner.i2c.read(I2C_ADDRESS, &mut buffer)?;
+            Ok(Measurement::from_raw_data(self.st1, buffer))
+        }
+    }
+
+    /// Moving average filter of continuous measurements that owns the driver
+    ///
+    /// Created by [`Ak09916::measure_moving_average`]. Each new measurement is averaged with the
+    /// previous ones, up to `N` measurements in total.
+    pub struct MeasurementMovingAvg<const N: usize, I: I2c, D: DelayNs> {
+        driver: Ak09916<I, D>,
+        mode: Mode,
+        poll_us: u32,
+        started: bool,
+        window: MeasurementQueue<N>,
+    }
+
+    impl<const N: usize, I: I2c, D: DelayNs> MeasurementMovingAvg<N, I, D> {
+        /// Polls a new measurement and returns the average of the last `N` measurements.
+        ///
+        /// The axes are rounded down, and the flags are the union of the flags of the averaged
+        /// measurements. If `N` is zero, the new measurement is returned as is.
+        pub fn next_measurement(&mut self) -> Result<Measurement, I::Error> {
+            if !self.started {
+                self.driver.switch_mode(self.mode)?;
+                self.started = true;
+            }
+            let measurement = self.driver.poll_measurement(self.poll_us)?;
+            self.window.push_back_overwrite(measurement);
+            Ok(self.window.mean().unwrap_or(measurement))
+        }
+        /// Consumes the filter and returns the driver.
+        ///
+        /// The device is left in the continuous mode, if it was already switched to it.
+        pub fn into_driver(self) -> Ak09916<I, D> {
+            self.driver
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regs::RegisterAddress;
+
+    fn measurement(hx: i16, hy: i16, hz: i16) -> Measurement {
+        Measurement {
+            hx,
+            hy,
+            hz,
+            flags: MeasurementFlags::empty(),
+        }
+    }
+
+    /// Test assertions for measurements
+    trait MeasurementAssertExt {
+        /// Asserts that all axes are within `tolerance_nt` nanoteslas of the expected measurement
+        fn assert_approx_eq(&self, expected: &Measurement, tolerance_nt: i32);
+    }
+
+    impl MeasurementAssertExt for Measurement {
+        #[track_caller]
+        fn assert_approx_eq(&self, expected: &Measurement, tolerance_nt: i32) {
+            assert!(
+                self.approx_eq(expected, tolerance_nt),
+                "{:?} is not within {} nT of {:?}",
+                self,
+                tolerance_nt,
+                expected
+            );
+        }
+    }
+
+    /// In-memory AK09916 register file with an auto-incrementing register pointer
+    #[derive(Clone, Debug)]
+    struct MockI2c {
+        regs: [u8; 0x40],
+        pointer: usize,
+        /// Number of writes that succeed before writes start failing
+        write_budget: Option<usize>,
+        /// Stays in single measurement mode after the measurement is done
+        single_measurement_stuck: bool,
+    }
+
+    impl MockI2c {
+        fn new() -> Self {
+            let mut regs = [0; 0x40];
+            regs[usize::from(u8::from(RegisterAddress::Wia1))] = WhoIAm::AK09916.company_id;
+            regs[usize::from(u8::from(RegisterAddress::Wia2))] = WhoIAm::AK09916.device_id;
+            MockI2c {
+                regs,
+                pointer: 0,
+                write_budget: None,
+                single_measurement_stuck: false,
+            }
+        }
+        fn register(&self, address: RegisterAddress) -> u8 {
+            self.regs[usize::from(u8::from(address))]
+        }
+        fn write_byte(&mut self, value: u8) {
+            if self.pointer == usize::from(u8::from(RegisterAddress::Cntl3)) && value & 1 != 0 {
+                // Soft reset clears the control registers and finishes immediately
+                *self = MockI2c {
+                    write_budget: self.write_budget,
+                    single_measurement_stuck: self.single_measurement_stuck,
+                    ..MockI2c::new()
+                };
+                return;
+            }
+            self.regs[self.pointer] = value;
+            if self.pointer == usize::from(u8::from(RegisterAddress::Cntl2))
+                && value == Mode::SingleMeasurement as u8
+            {
+                // The measurement finishes immediately
+                self.regs[usize::from(u8::from(RegisterAddress::St1))] |= 1;
+                if !self.single_measurement_stuck {
+                    self.regs[self.pointer] = Mode::PowerDown as u8;
+                }
+            }
+            self.pointer += 1;
+        }
+    }
+
+    impl embedded_hal::i2c::ErrorType for MockI2c {
+        type Error = embedded_hal::i2c::ErrorKind;
+    }
+
+    impl embedded_hal::i2c::I2c for MockI2c {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+            if address != I2C_ADDRESS {
+                return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+            }
+            for operation in operations {
+                match operation {
+                    Operation::Write(_) if self.write_budget == Some(0) => {
+                        return Err(ErrorKind::Bus)
+                    }
+                    Operation::Write(bytes) => {
+                        if let Some(budget) = &mut self.write_budget {
+                            *budget -= 1;
+                        }
+                        if let Some((&pointer, data)) = bytes.split_first() {
+                            self.pointer = usize::from(pointer);
+                            for &value in data {
+                                self.write_byte(value);
+                            }
+                        }
+                    }
+                    Operation::Read(buffer) => {
+                        for value in buffer.iter_mut() {
+                            *value = self.regs[self.pointer];
+                            self.pointer += 1;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl embedded_hal_async::i2c::I2c for MockI2c {
+        async fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            embedded_hal::i2c::I2c::transaction(self, address, operations)
+        }
+    }
+
+    /// Delay that returns immediately
+    #[derive(Clone, Copy, Debug)]
+    struct NoDelay;
+
+    impl embedded_hal::delay::DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    impl embedded_hal_async::delay::DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    fn driver() -> blocking::Ak09916<MockI2c, NoDelay> {
+        blocking::Ak09916::new(MockI2c::new(), NoDelay)
+    }
+
+    #[test]
+    fn approx_eq() {
+        let m = measurement(100, -100, 0);
+        m.assert_approx_eq(&measurement(101, -99, -1), 150);
+        m.assert_approx_eq(&m, 0);
+        assert!(!m.approx_eq(&measurement(101, -100, 0), 149));
+        assert!(!m.approx_eq(&measurement(100, -100, 2), 150));
+        measurement(i16::MIN, 0, 0).assert_approx_eq(&measurement(i16::MAX, 0, 0), 150 * 65535);
+    }
+
+    #[test]
+    fn stats_mean_and_variance() {
+        let mut stats = MeasurementStats::new();
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.variance_x(), 0);
+        for value in [2, 4, 4, 4, 5, 5, 7, 9] {
+            stats.push(measurement(value, -value, 100));
+        }
+        assert_eq!(stats.count(), 8);
+        assert_eq!(stats.mean(), Some(measurement(5, -5, 100)));
+        assert_eq!(stats.variance_x(), 4);
+        assert_eq!(stats.variance_y(), 4);
+        assert_eq!(stats.variance_z(), 0);
+        assert_eq!(stats.std_dev_approx_x(), 2);
+        assert_eq!(stats.std_dev_approx_y(), 2);
+        assert_eq!(stats.std_dev_approx_z(), 0);
+    }
+
+    #[test]
+    fn stats_extreme_values() {
+        let mut stats = MeasurementStats::new();
+        stats.push(measurement(i16::MIN, i16::MAX, -1));
+        stats.push(measurement(i16::MAX, i16::MIN, -2));
+        // mean is rounded down
+        assert_eq!(stats.mean(), Some(measurement(-1, -1, -2)));
+        assert_eq!(stats.variance_x(), 1_073_709_056);
+        assert_eq!(stats.variance_y(), 1_073_709_056);
+        assert_eq!(stats.variance_z(), 0);
+    }
+
+    #[test]
+    fn stats_flags_are_combined() {
+        let mut stats = MeasurementStats::new();
+        stats.push(Measurement {
+            flags: MeasurementFlags::OVERRUN,
+            ..measurement(1, 1, 1)
+        });
+        stats.push(Measurement {
+            flags: MeasurementFlags::OVERFLOW,
+            ..measurement(1, 1, 1)
+        });
+        assert_eq!(
+            stats.mean().unwrap().flags,
+            MeasurementFlags::all_error_flags()
+        );
+    }
+
+    #[test]
+    fn switch_mode_caches_continuous_modes() {
+        let mut driver = driver();
+        assert_eq!(driver.current_mode(), None);
+        driver.switch_mode(Mode::Continuous50Hz).unwrap();
+        assert_eq!(driver.current_mode(), Some(Mode::Continuous50Hz));
+        driver.switch_mode(Mode::PowerDown).unwrap();
+        assert_eq!(driver.current_mode(), Some(Mode::PowerDown));
+    }
+
+    #[test]
+    fn switch_mode_does_not_cache_single_measurement_or_self_test() {
+        let mut driver = driver();
+        for mode in [Mode::SingleMeasurement, Mode::SelfTest] {
+            driver.switch_mode(Mode::Continuous10Hz).unwrap();
+            driver.switch_mode(mode).unwrap();
+            assert_eq!(driver.current_mode(), None);
+        }
+    }
+
+    #[test]
+    fn cntl2_write_updates_cached_mode() {
+        let mut driver = driver();
+        driver
+            .write_register8(regs::Cntl2::from(Mode::Continuous100Hz))
+            .unwrap();
+        assert_eq!(driver.current_mode(), Some(Mode::Continuous100Hz));
+        let (i2c, _) = driver.into_inner();
+        assert_eq!(
+            i2c.register(RegisterAddress::Cntl2),
+            Mode::Continuous100Hz as u8
+        );
+    }
+
+    #[test]
+    fn cntl3_write_clears_cached_mode() {
+        let mut driver = driver();
+        driver.switch_mode(Mode::Continuous20Hz).unwrap();
+        driver.write_register8(regs::Cntl3::SRST).unwrap();
+        assert_eq!(driver.current_mode(), None);
+        driver.switch_mode(Mode::Continuous20Hz).unwrap();
+        driver.soft_reset().unwrap();
+        assert_eq!(driver.current_mode(), None);
+    }
+
+    #[test]
+    fn failed_write_clears_cached_mode() {
+        let mut i2c = MockI2c::new();
+        i2c.write_budget = Some(3);
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        driver.switch_mode(Mode::Continuous20Hz).unwrap();
+        assert_eq!(driver.current_mode(), Some(Mode::Continuous20Hz));
+        // The power-down write succeeds, but the write of the new mode fails
+        driver.switch_mode(Mode::Continuous50Hz).unwrap_err();
+        assert_eq!(driver.current_mode(), None);
+    }
+
+    #[test]
+    fn from_nanoteslas_rounds_half_away_from_zero() {
+        assert_eq!(
+            Measurement::from_nanoteslas(74, 75, 224),
+            measurement(0, 1, 1)
+        );
+        assert_eq!(
+            Measurement::from_nanoteslas(-74, -75, -224),
+            measurement(0, -1, -1)
+        );
+        assert_eq!(
+            Measurement::from_nanoteslas(225, -225, 0),
+            measurement(2, -2, 0)
+        );
+    }
+
+    #[test]
+    fn from_nanoteslas_saturates() {
+        let max = i32::from(i16::MAX) * 150;
+        let min = i32::from(i16::MIN) * 150;
+        assert_eq!(
+            Measurement::from_nanoteslas(max + 74, max + 75, i32::MAX),
+            measurement(i16::MAX, i16::MAX, i16::MAX)
+        );
+        assert_eq!(
+            Measurement::from_nanoteslas(min - 74, min - 75, i32::MIN),
+            measurement(i16::MIN, i16::MIN, i16::MIN)
+        );
+    }
+
+    #[test]
+    fn from_microteslas_rounds_and_saturates() {
+        assert_eq!(
+            Measurement::from_microteslas(1, -1, 0),
+            measurement(7, -7, 0)
+        );
+        assert_eq!(
+            Measurement::from_microteslas(3, -3, 5),
+            measurement(20, -20, 33)
+        );
+        assert_eq!(
+            Measurement::from_microteslas(i32::MAX, i32::MIN, 4916),
+            measurement(i16::MAX, i16::MIN, i16::MAX)
+        );
+    }
+
+    #[test]
+    fn from_gauss_scaled_rounds_and_saturates() {
+        // 0.1 mG units, so 7 and 8 are 70 nT and 80 nT
+        assert_eq!(
+            Measurement::from_gauss_scaled(7, 8, -8, 10_000),
+            measurement(0, 1, -1)
+        );
+        // 1 nT units, so 75 is exactly half of the resolution
+        assert_eq!(
+            Measurement::from_gauss_scaled(75, -75, 74, 100_000),
+            measurement(1, -1, 0)
+        );
+        assert_eq!(
+            Measurement::from_gauss_scaled(1, -1, 0, -1),
+            measurement(-667, 667, 0)
+        );
+        assert_eq!(
+            Measurement::from_gauss_scaled(i32::MAX, i32::MIN, 50, 1),
+            measurement(i16::MAX, i16::MIN, i16::MAX)
+        );
+    }
+
+    #[track_caller]
+    fn assert_angle(y: i64, x: i64, expected: i32) {
+        let angle = atan2_millidegrees(y, x);
+        assert!(
+            (angle - expected).abs() <= 5,
+            "atan2({}, {}) = {}, expected {}",
+            y,
+            x,
+            angle,
+            expected
+        );
+    }
+
+    #[test]
+    fn atan2_axes_and_origin() {
+        assert_eq!(atan2_millidegrees(0, 0), 0);
+        assert_angle(0, 1, 0);
+        assert_angle(1, 0, 90_000);
+        assert_angle(0, -1, 180_000);
+        assert_angle(-1, 0, -90_000);
+        assert_eq!(atan2_millidegrees(0, -1), 180_000);
+    }
+
+    #[test]
+    fn atan2_quadrants() {
+        assert_angle(1, 1, 45_000);
+        assert_angle(1, 2, 26_565);
+        assert_angle(2, 1, 63_435);
+        assert_angle(1, -1, 135_000);
+        assert_angle(3, -4, 143_130);
+        assert_angle(-1, -1, -135_000);
+        assert_angle(-3, -4, -143_130);
+        assert_angle(-1, 1, -45_000);
+        assert_angle(-5, 12, -22_620);
+    }
+
+    #[test]
+    fn atan2_extreme_inputs() {
+        let (min, max) = (i64::from(i16::MIN), i64::from(i16::MAX));
+        assert_angle(min, min, -135_000);
+        assert_angle(0, min, 180_000);
+        assert_angle(min, 0, -90_000);
+        assert_angle(max, min, 135_001);
+        assert_angle(min, max, -45_001);
+        assert_angle(min, 1, -89_998);
+        assert_angle(1, min, 179_998);
+        assert_angle(-1, min, -179_998);
+        assert_angle(1 << 40, -(1 << 40), 135_000);
+    }
+
+    #[test]
+    fn heading_enu_and_ned() {
+        assert_eq!(measurement(0, 0, 100).heading_enu(), None);
+        assert_eq!(measurement(0, 0, 100).heading_ned(), None);
+        // ENU: heading of the Y-axis, field points north
+        assert_eq!(measurement(0, 100, 0).heading_enu(), Some(0));
+        assert_eq!(measurement(-100, 0, 0).heading_enu(), Some(900));
+        assert_eq!(measurement(0, -100, 0).heading_enu(), Some(1800));
+        assert_eq!(measurement(100, 0, 0).heading_enu(), Some(2700));
+        assert_eq!(measurement(-100, 100, 0).heading_enu(), Some(450));
+        // NED: heading of the X-axis
+        assert_eq!(measurement(100, 0, 0).heading_ned(), Some(0));
+        assert_eq!(measurement(0, -100, 0).heading_ned(), Some(900));
+        assert_eq!(measurement(-100, 0, 0).heading_ned(), Some(1800));
+        assert_eq!(measurement(0, 100, 0).heading_ned(), Some(2700));
+        assert_eq!(measurement(i16::MIN, i16::MIN, 0).heading_ned(), Some(1350));
+    }
+
+    #[test]
+    fn tilt_compensated_heading_reference_angles() {
+        // Field of (200, 0, 400) in North-East-Down world coordinates, seen by a device with the
+        // given heading, pitch and roll
+        let cases = [
+            (300, (173, -100, 400), (0, 0, -1000)),
+            (300, (26, -100, 435), (342, 0, -940)),
+            (1200, (113, -65, 428), (-500, -224, -837)),
+            (2100, (-240, -157, 343), (174, 633, -754)),
+            (3000, (-212, 327, 220), (707, -354, -612)),
+            (0, (-246, 0, 373), (866, 0, -500)),
+            (900, (0, 359, 266), (0, -985, -174)),
+        ];
+        for (expected, (hx, hy, hz), (ax, ay, az)) in cases {
+            let heading = measurement(hx, hy, hz)
+                .tilt_compensated_heading(ax, ay, az)
+                .unwrap();
+            let error = (heading - expected + 1800).rem_euclid(3600) - 1800;
+            assert!(error.abs() <= 5, "{} != {}", heading, expected);
+        }
+    }
+
+    #[test]
+    fn tilt_compensated_heading_level_matches_heading_ned() {
+        for m in [
+            measurement(100, 0, 50),
+            measurement(-100, 100, 50),
+            measurement(0, -100, -50),
+            measurement(i16::MIN, i16::MIN, i16::MIN),
+        ] {
+            assert_eq!(m.tilt_compensated_heading(0, 0, -1000), m.heading_ned());
+            assert_eq!(m.tilt_compensated_heading(0, 0, i32::MIN), m.heading_ned());
+        }
+    }
+
+    #[test]
+    fn tilt_compensated_heading_undefined() {
+        let m = measurement(100, 200, 300);
+        assert_eq!(m.tilt_compensated_heading(0, 0, 0), None);
+        assert_eq!(m.tilt_compensated_heading(1000, 0, 0), None);
+        assert_eq!(m.tilt_compensated_heading(-1000, 0, 0), None);
+        assert_eq!(
+            measurement(0, 0, 0).tilt_compensated_heading(0, 0, -1000),
+            None
+        );
+    }
+
+    #[test]
+    fn queue_wraps_around() {
+        let mut queue = MeasurementQueue::<3>::new();
+        for round in 0..4 {
+            for i in 0..3 {
+                queue.push_back(measurement(round, i, 0)).unwrap();
+            }
+            assert!(queue.is_full());
+            assert_eq!(
+                queue.push_back(measurement(-1, -1, -1)),
+                Err(measurement(-1, -1, -1))
+            );
+            assert_eq!(queue.pop_front(), Some(measurement(round, 0, 0)));
+            queue.push_back(measurement(round, 3, 0)).unwrap();
+            let expected = [1, 2, 3].map(|i| measurement(round, i, 0));
+            assert!(queue.iter().eq(expected.iter()));
+            for m in expected {
+                assert_eq!(queue.pop_front(), Some(m));
+            }
+            assert!(queue.is_empty());
+            assert_eq!(queue.pop_front(), None);
+        }
+    }
+
+    #[test]
+    fn queue_push_back_overwrite() {
+        let mut queue = MeasurementQueue::<2>::new();
+        for i in 0..5 {
+            queue.push_back_overwrite(measurement(i, 0, 0));
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.mean(), Some(measurement(3, 0, 0)));
+        assert_eq!(queue.pop_front(), Some(measurement(3, 0, 0)));
+        assert_eq!(queue.pop_front(), Some(measurement(4, 0, 0)));
+        assert_eq!(queue.mean(), None);
+    }
+
+    #[test]
+    fn queue_zero_capacity() {
+        let mut queue = MeasurementQueue::<0>::new();
+        assert!(queue.is_empty());
+        assert!(queue.is_full());
+        assert_eq!(
+            queue.push_back(measurement(1, 2, 3)),
+            Err(measurement(1, 2, 3))
+        );
+        queue.push_back_overwrite(measurement(1, 2, 3));
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.pop_front(), None);
+        assert_eq!(queue.iter().next(), None);
+        assert_eq!(queue.mean(), None);
+    }
+
+    fn register_dump_bytes(st1: u8, st2: u8) -> [u8; 16] {
+        [
+            0x48, 0x09, 0x00, 0x00, st1, 0x34, 0x12, 0xcc, 0xfe, 0x00, 0x80, 0x00, st2, 0x00, 0x08,
+            0x00,
+        ]
+    }
+
+    #[test]
+    fn register_dump_to_measurement() {
+        let cases = [
+            (0x00, 0x00, MeasurementFlags::empty()),
+            (0x01, 0x00, MeasurementFlags::empty()),
+            (0x03, 0x00, MeasurementFlags::OVERRUN),
+            (0x01, 0x08, MeasurementFlags::OVERFLOW),
+            (
+                0x02,
+                0x08,
+                MeasurementFlags::OVERFLOW | MeasurementFlags::OVERRUN,
+            ),
+        ];
+        for (st1, st2, flags) in cases {
+            let dump = regs::RegisterDump::from_raw_data(register_dump_bytes(st1, st2));
+            assert_eq!(
+                dump.to_measurement(),
+                Measurement {
+                    hx: 0x1234,
+                    hy: -308,
+                    hz: i16::MIN,
+                    flags,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn drivers_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<blocking::Ak09916<MockI2c, NoDelay>>();
+        assert_send::<asynch::Ak09916<MockI2c, NoDelay>>();
+        assert_send::<blocking::Ak09916<&mut MockI2c, &mut NoDelay>>();
+        assert_send::<asynch::Ak09916<&mut MockI2c, &mut NoDelay>>();
+    }
+
+    #[test]
+    fn measure_and_power_down() {
+        let mut i2c = MockI2c::new();
+        i2c.regs[0x11..0x17].copy_from_slice(&[0x64, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        assert_eq!(
+            driver.measure_and_power_down(10, 1).unwrap(),
+            measurement(100, 0, 0)
+        );
+        assert_eq!(driver.current_mode(), Some(Mode::PowerDown));
+        assert_eq!(driver.measure_and_compute_heading(10, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn measure_and_power_down_times_out() {
+        let mut i2c = MockI2c::new();
+        i2c.single_measurement_stuck = true;
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        assert!(matches!(
+            driver.measure_and_power_down(10, 3),
+            Err(Ak09916Error::Timeout)
+        ));
+        assert_eq!(driver.current_mode(), None);
+        assert!(matches!(
+            driver.measure_and_power_down(10, 0),
+            Err(Ak09916Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn who_i_am_retries() {
+        let mut driver = driver();
+        assert_eq!(driver.wait_for_device(0, 10).unwrap(), WhoIAm::AK09916);
+        assert_eq!(driver.read_who_i_am_loop(0, 10).unwrap(), WhoIAm::AK09916);
+
+        let mut i2c = MockI2c::new();
+        i2c.regs[1] = 0x0c;
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        assert!(matches!(
+            driver.read_who_i_am_loop(3, 10),
+            Err(Ak09916Error::WrongDevice(WhoIAm {
+                company_id: 0x48,
+                device_id: 0x0c
+            }))
+        ));
+
+        let mut i2c = MockI2c::new();
+        i2c.write_budget = Some(0);
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        assert!(matches!(
+            driver.wait_for_device(3, 10),
+            Err(Ak09916Error::DeviceNotFound)
+        ));
+        assert!(matches!(
+            driver.read_who_i_am_loop(3, 10),
+            Err(Ak09916Error::I2c(embedded_hal::i2c::ErrorKind::Bus))
+        ));
+    }
+
+    #[test]
+    fn projections_recombine_to_original() {
+        for m in [
+            measurement(100, -200, 300),
+            measurement(i16::MIN, i16::MAX, 0),
+            Measurement {
+                flags: MeasurementFlags::OVERFLOW | MeasurementFlags::OVERRUN,
+                ..measurement(-1, 1, i16::MIN)
+            },
+        ] {
+            let mut sum = [0; 3];
+            for axis in [Axis::X, Axis::Y, Axis::Z] {
+                let projection = m.project_onto_axis(axis);
+                assert_eq!(projection.flags, m.flags);
+                assert_eq!(projection[axis], m[axis]);
+                for other in [Axis::X, Axis::Y, Axis::Z] {
+                    if other != axis {
+                        assert_eq!(projection[other], 0);
+                    }
+                }
+                for (sum, value) in sum.iter_mut().zip(projection.to_array()) {
+                    *sum += i32::from(value);
+                }
+            }
+            assert_eq!(sum, m.to_array().map(i32::from));
+        }
+    }
+
+    #[test]
+    fn from_register_dump_matches_from_raw_data() {
+        for st1 in [0x00, 0x01, 0x02, 0x03, 0xff] {
+            for st2 in [0x00, 0x08, 0xf7, 0xff] {
+                let bytes = register_dump_bytes(st1, st2);
+                let dump = regs::RegisterDump::from_raw_data(bytes);
+                let mut data = [0; 8];
+                data.copy_from_slice(&bytes[5..13]);
+                let raw = Measurement::from_raw_data(regs::St1::from(st1), data);
+                assert_eq!(Measurement::from_register_dump(&dump), raw);
+                assert_eq!(raw.overrun(), st1 & 0x02 != 0);
+                assert_eq!(raw.overflow(), st2 & 0x08 != 0);
+            }
+        }
+    }
+
+    /// Sensor that returns queued measurements in continuous modes
+    struct MockMagSensor {
+        mode: Mode,
+        measurements: MeasurementQueue<4>,
+    }
+
+    impl MagSensor for MockMagSensor {
+        type Error = core::convert::Infallible;
+
+        fn read_measurement(&mut self) -> Result<Option<Measurement>, Self::Error> {
+            if Mode::continuous_variants().contains(&self.mode) {
+                Ok(self.measurements.pop_front())
+            } else {
+                Ok(None)
+            }
+        }
+        fn switch_mode(&mut self, mode: Mode) -> Result<(), Self::Error> {
+            self.mode = mode;
+            Ok(())
+        }
+    }
+
+    /// Reads up to `N` measurements in continuous mode and powers the sensor down
+    fn read_continuous<E, const N: usize>(
+        sensor: &mut dyn MagSensor<Error = E>,
+    ) -> Result<MeasurementQueue<N>, E> {
+        let mut queue = MeasurementQueue::new();
+        sensor.switch_mode(Mode::Continuous100Hz)?;
+        while let Some(measurement) = sensor.read_measurement()? {
+            if queue.push_back(measurement).is_err() {
+                break;
+            }
+        }
+        sensor.switch_mode(Mode::PowerDown)?;
+        Ok(queue)
+    }
+
+    #[test]
+    fn mag_sensor_trait_object() {
+        let mut sensor = MockMagSensor {
+            mode: Mode::PowerDown,
+            measurements: MeasurementQueue::new(),
+        };
+        for i in 0..3 {
+            sensor
+                .measurements
+                .push_back(measurement(i, -i, 0))
+                .unwrap();
+        }
+        assert_eq!(sensor.read_measurement(), Ok(None));
+        let queue = read_continuous::<_, 2>(&mut sensor).unwrap();
+        assert!(queue
+            .iter()
+            .eq([measurement(0, 0, 0), measurement(1, -1, 0)].iter()));
+        assert_eq!(sensor.mode, Mode::PowerDown);
+
+        let mut driver = driver();
+        let queue = read_continuous::<_, 2>(&mut driver).unwrap();
+        assert!(queue.is_empty());
+        assert_eq!(driver.current_mode(), Some(Mode::PowerDown));
+    }
+
+    #[test]
+    fn overflow_callback() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+        fn count_overflow(measurement: &Measurement) {
+            assert!(measurement.overflow());
+            OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut i2c = MockI2c::new();
+        i2c.regs[usize::from(u8::from(RegisterAddress::St1))] = 0x01;
+        i2c.regs[usize::from(u8::from(RegisterAddress::St2))] = 0x08;
+        let mut driver = blocking::Ak09916::new(i2c, NoDelay);
+        driver.on_overflow(Some(count_overflow));
+        assert!(driver.read_measurement().unwrap().unwrap().overflow());
+        assert!(driver.poll_measurement(10).unwrap().overflow());
+        assert_eq!(OVERFLOWS.load(Ordering::Relaxed), 2);
+        assert!(driver.read_measurement_unchecked().unwrap().overflow());
+        assert!(driver.read_into_buffer(&mut [0; 8]).unwrap().overflow());
+        assert_eq!(OVERFLOWS.load(Ordering::Relaxed), 2);
+        driver.on_overflow(None);
+        driver.read_measurement().unwrap();
+        assert_eq!(OVERFLOWS.load(Ordering::Relaxed), 2);
+    }
+
+    /// Delay that advances a shared clock instead of waiting
+    struct ClockDelay<'a>(&'a core::cell::Cell<u64>);
+
+    impl embedded_hal::delay::DelayNs for ClockDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + u64::from(ns));
+        }
+    }
+
+    /// Bus that records the clock time and value of each `CNTL2` write
+    struct Cntl2WriteLog<'a> {
+        i2c: MockI2c,
+        clock: &'a core::cell::Cell<u64>,
+        writes: [(u64, u8); 8],
+        len: usize,
+    }
+
+    impl embedded_hal::i2c::ErrorType for Cntl2WriteLog<'_> {
+        type Error = embedded_hal::i2c::ErrorKind;
+    }
+
+    impl embedded_hal::i2c::I2c for Cntl2WriteLog<'_> {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations.iter() {
+                if let embedded_hal::i2c::Operation::Write(&[0x31, value]) = operation {
+                    self.writes[self.len] = (self.clock.get() / 1000, value);
+                    self.len += 1;
+                }
+            }
+            self.i2c.transaction(address, operations)
+        }
+    }
+
+    #[test]
+    fn reconfigure_mode_waits_after_power_down() {
+        let clock = core::cell::Cell::new(0);
+        let i2c = Cntl2WriteLog {
+            i2c: MockI2c::new(),
+            clock: &clock,
+            writes: [(0, 0); 8],
+            len: 0,
+        };
+        let mut driver = blocking::Ak09916::new(i2c, ClockDelay(&clock));
+        driver.switch_mode(Mode::Continuous10Hz).unwrap();
+        driver.power_down().unwrap();
+        driver.reconfigure_mode(Mode::Continuous50Hz).unwrap();
+        // Mode is unknown, but the read back shows power-down mode
+        let (mut i2c, delay) = driver.into_inner();
+        i2c.i2c.regs[0x31] = Mode::PowerDown as u8;
+        let mut driver = blocking::Ak09916::new(i2c, delay);
+        driver.reconfigure_mode(Mode::Continuous100Hz).unwrap();
+        let (i2c, _) = driver.into_inner();
+        assert_eq!(
+            i2c.writes[..i2c.len],
+            [
+                (0, Mode::PowerDown as u8),
+                (100, Mode::Continuous10Hz as u8),
+                (100, Mode::PowerDown as u8),
+                (200, Mode::Continuous50Hz as u8),
+                (300, Mode::Continuous100Hz as u8),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_to_unit_q15_edge_cases() {
+        assert_eq!(measurement(0, 0, 0).normalize_to_unit_q15(), [0; 3]);
+        assert_eq!(
+            measurement(i16::MIN, 0, 0).normalize_to_unit_q15(),
+            [-32767, 0, 0]
+        );
+        assert_eq!(
+            measurement(0, 0, i16::MIN).normalize_to_unit_q15(),
+            [0, 0, -32767]
+        );
+        assert_eq!(
+            measurement(i16::MAX, 0, 0).normalize_to_unit_q15(),
+            [32767, 0, 0]
+        );
+        assert_eq!(
+            measurement(-1, 0, 0).normalize_to_unit_q15(),
+            [-32767, 0, 0]
+        );
+        assert_eq!(
+            measurement(3, 4, 0).normalize_to_unit_q15(),
+            [19660, 26213, 0]
+        );
+        assert_eq!(
+            measurement(i16::MIN, i16::MIN, i16::MIN).normalize_to_unit_q15(),
+            [-18918; 3]
+        );
+    }
+
+    #[test]
+    fn into_frame_saturates_negation() {
+        let m = Measurement {
+            flags: MeasurementFlags::OVERFLOW,
+            ..measurement(1, 2, i16::MIN)
+        };
+        let enu = m.into_frame(CoordinateFrame::Ned, CoordinateFrame::Enu);
+        assert_eq!(enu.to_array(), [2, 1, i16::MAX]);
+        assert_eq!(enu.flags, MeasurementFlags::OVERFLOW);
+        let nwu = measurement(i16::MIN, i16::MIN, i16::MIN)
+            .into_frame(CoordinateFrame::SensorDefault, CoordinateFrame::Nwu);
+        assert_eq!(nwu.to_array(), [i16::MIN, i16::MAX, i16::MAX]);
+        let m = measurement(100, -200, 300);
+        let round_trip = m
+            .into_frame(CoordinateFrame::Ned, CoordinateFrame::Enu)
+            .into_frame(CoordinateFrame::Enu, CoordinateFrame::Nwu)
+            .into_frame(CoordinateFrame::Nwu, CoordinateFrame::Ned);
+        assert_eq!(round_trip, m);
+    }
+
+    #[test]
+    fn compass_point_sector_boundaries() {
+        // Sectors are 22.5° wide, so the boundaries are 11.25° from each point
+        for (i, point) in CompassPoint::ALL.iter().enumerate() {
+            let center = i as i32 * 225;
+            assert_eq!(CompassPoint::from_heading(center), *point);
+            assert_eq!(CompassPoint::from_heading(center - 112), *point);
+            assert_eq!(CompassPoint::from_heading(center + 112), *point);
+            assert_eq!(
+                CompassPoint::from_heading(center + 113),
+                CompassPoint::ALL[(i + 1) % 16]
+            );
+        }
+        assert_eq!(
+            CompassPoint::from_heading(3487),
+            CompassPoint::NorthNorthwest
+        );
+        assert_eq!(CompassPoint::from_heading(3488), CompassPoint::North);
+        assert_eq!(CompassPoint::from_heading(3600), CompassPoint::North);
+        assert_eq!(
+            CompassPoint::from_heading(-113),
+            CompassPoint::NorthNorthwest
+        );
+        assert_eq!(CompassPoint::from_heading(i32::MIN), CompassPoint::West);
+        assert_eq!(CompassPoint::from_heading(i32::MAX), CompassPoint::East);
+    }
+
+    #[test]
+    fn interpolate_edge_cases() {
+        let a = Measurement {
+            flags: MeasurementFlags::OVERRUN,
+            ..measurement(i16::MIN, 0, 100)
+        };
+        let b = Measurement {
+            flags: MeasurementFlags::OVERFLOW,
+            ..measurement(i16::MAX, -1, -100)
+        };
+        let flags = MeasurementFlags::OVERFLOW | MeasurementFlags::OVERRUN;
+        assert_eq!(a.interpolate(&b, 0), Measurement { flags, ..a });
+        assert_eq!(a.interpolate(&b, i16::MIN), Measurement { flags, ..a });
+        assert_eq!(a.interpolate(&b, i16::MAX), Measurement { flags, ..b });
+        assert_eq!(a.interpolate(&b, 16384).to_array(), [1, -1, 0]);
+        assert_eq!(a.interpolate(&b, 16383).to_array(), [-2, 0, 0]);
+    }
+
+    #[test]
+    fn scale_nt_edge_cases() {
+        let m = Measurement {
+            flags: MeasurementFlags::OVERFLOW,
+            ..measurement(100, -100, i16::MIN)
+        };
+        assert_eq!(m.scale_nt(i16::MAX), m);
+        assert_eq!(m.scale_nt(0).to_array(), [0; 3]);
+        let negated = m.scale_nt(i16::MIN);
+        assert_eq!(negated.to_array(), [-100, 100, i16::MAX]);
+        assert_eq!(negated.flags, MeasurementFlags::OVERFLOW);
+        assert_eq!(
+            measurement(i16::MIN, i16::MAX, 1)
+                .scale_nt(i16::MIN)
+                .to_array(),
+            [i16::MAX, i16::MIN, -1]
+        );
+        assert_eq!(
+            measurement(i16::MIN, 0, 0).scale_nt(-1).to_array(),
+            [1, 0, 0]
+        );
+        assert_eq!(measurement(3, -3, 1).scale_nt(16384).to_array(), [2, -2, 1]);
+        assert_eq!(
+            measurement(1, -1, 0).scale_nt(-16384).to_array(),
+            [-1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn register_dump_validate_reserved_bits() {
+        let validate = |bytes| regs::RegisterDump::from_raw_data(bytes).validate();
+        assert_eq!(validate(register_dump_bytes(0x03, 0x08)), Ok(()));
+        let reserved =
+            |register, bits| Err(regs::RegisterDumpError::ReservedBitsSet { register, bits });
+        assert_eq!(
+            validate(register_dump_bytes(0x81, 0x00)),
+            reserved(RegisterAddress::St1, 0x80)
+        );
+        assert_eq!(
+            validate(register_dump_bytes(0x01, 0x78)),
+            reserved(RegisterAddress::St2, 0x70)
+        );
+        let mut bytes = register_dump_bytes(0x01, 0x00);
+        bytes[15] = 0x02;
+        assert_eq!(validate(bytes), reserved(RegisterAddress::Cntl3, 0x02));
+        // Earlier checks take precedence
+        bytes[1] = 0x0c;
+        assert_eq!(
+            validate(bytes),
+            Err(regs::RegisterDumpError::UnexpectedDeviceId(0x0c))
+        );
+    }
+
+    /// Fixed-size buffer for testing formatting without allocation
+    struct FmtBuffer {
+        buffer: [u8; 256],
+        len: usize,
+    }
+
+    impl FmtBuffer {
+        fn new() -> Self {
+            FmtBuffer {
+                buffer: [0; 256],
+                len: 0,
+            }
+        }
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.buffer[..self.len]).unwrap()
+        }
+    }
+
+    impl fmt::Write for FmtBuffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buffer
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn register_dump_debug_uses_register_names() {
+        use core::fmt::Write;
+        let dump = regs::RegisterDump::from_raw_data(register_dump_bytes(0x01, 0x08));
+        let mut buffer = FmtBuffer::new();
+        write!(buffer, "{:?}", dump).unwrap();
+        assert_eq!(
+            buffer.as_str(),
+            "RegisterDump { Wia1(0x00)=0x48, Wia2(0x01)=0x09, St1(0x10)=0b00000001, \
+             Hxl(0x11)=4660, Hyl(0x13)=-308, Hzl(0x15)=-32768, St2(0x18)=0b00001000, \
+             Cntl2(0x31)=0x08, Cntl3(0x32)=0b00000000 }"
+        );
+        let mut buffer = FmtBuffer::new();
+        write!(buffer, "{}", RegisterAddress::Cntl2).unwrap();
+        assert_eq!(buffer.as_str(), "Cntl2(0x31)");
+    }
+
+    #[test]
+    fn restore_state_writes_mode_even_if_cached() {
+        let clock = core::cell::Cell::new(0);
+        let i2c = Cntl2WriteLog {
+            i2c: MockI2c::new(),
+            clock: &clock,
+            writes: [(0, 0); 8],
+            len: 0,
+        };
+        let mut driver = blocking::Ak09916::new(i2c, ClockDelay(&clock));
+        driver.switch_mode(Mode::Continuous50Hz).unwrap();
+        let state = driver.save_state();
+        assert_eq!(state.mode, Some(Mode::Continuous50Hz));
+        driver.restore_state(state).unwrap();
+        assert_eq!(driver.current_mode(), Some(Mode::Continuous50Hz));
+        driver
+            .restore_state(DriverState {
+                mode: None,
+                calibration: None,
+            })
+            .unwrap();
+        assert_eq!(driver.current_mode(), None);
+        let (i2c, _) = driver.into_inner();
+        assert_eq!(
+            i2c.writes[..i2c.len],
+            [
+                (0, Mode::PowerDown as u8),
+                (100, Mode::Continuous50Hz as u8),
+                (100, Mode::PowerDown as u8),
+                (200, Mode::Continuous50Hz as u8),
+            ]
+        );
     }
 }
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! Low-level register definitions
+use core::{cmp::Ordering, fmt};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 #[cfg(feature = "defmt-1")]
@@ -14,7 +15,7 @@ use bitflags::bitflags as bitflags_macro;
 #[cfg(feature = "defmt-1")]
 use crate::defmt::bitflags as bitflags_macro;
 
-use super::Mode;
+use super::{Measurement, MeasurementFlags, Mode};
 
 /// Register address
 #[repr(u8)]
@@ -58,10 +59,68 @@ pub enum RegisterAddress {
     Ts2 = 0x34,
 }
 
+impl RegisterAddress {
+    /// All readable register addresses, excluding reserved and test registers
+    pub const READABLE: [RegisterAddress; 14] = [
+        RegisterAddress::Wia1,
+        RegisterAddress::Wia2,
+        RegisterAddress::St1,
+        RegisterAddress::Hxl,
+        RegisterAddress::Hxh,
+        RegisterAddress::Hyl,
+        RegisterAddress::Hyh,
+        RegisterAddress::Hzl,
+        RegisterAddress::Hzh,
+        RegisterAddress::Tmps,
+        RegisterAddress::St2,
+        RegisterAddress::Cntl1,
+        RegisterAddress::Cntl2,
+        RegisterAddress::Cntl3,
+    ];
+
+    /// Returns an iterator over all readable register addresses, excluding reserved and test
+    /// registers
+    pub fn readable_iter() -> impl Iterator<Item = RegisterAddress> {
+        RegisterAddress::READABLE.into_iter()
+    }
+}
+
+impl RegisterAddress {
+    fn name(self) -> &'static str {
+        match self {
+            RegisterAddress::Wia1 => "Wia1",
+            RegisterAddress::Wia2 => "Wia2",
+            RegisterAddress::Rsv1 => "Rsv1",
+            RegisterAddress::Rsv2 => "Rsv2",
+            RegisterAddress::St1 => "St1",
+            RegisterAddress::Hxl => "Hxl",
+            RegisterAddress::Hxh => "Hxh",
+            RegisterAddress::Hyl => "Hyl",
+            RegisterAddress::Hyh => "Hyh",
+            RegisterAddress::Hzl => "Hzl",
+            RegisterAddress::Hzh => "Hzh",
+            RegisterAddress::Tmps => "Tmps",
+            RegisterAddress::St2 => "St2",
+            RegisterAddress::Cntl1 => "Cntl1",
+            RegisterAddress::Cntl2 => "Cntl2",
+            RegisterAddress::Cntl3 => "Cntl3",
+            RegisterAddress::Ts1 => "Ts1",
+            RegisterAddress::Ts2 => "Ts2",
+        }
+    }
+}
+
+/// Formats the register name and address, e.g. `Cntl2(0x31)`
+impl fmt::Display for RegisterAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({:#04x})", self.name(), u8::from(*self))
+    }
+}
+
 #[cfg(feature = "defmt-1")]
 impl defmt::Format for RegisterAddress {
     fn format(&self, fmt: defmt::Formatter) {
-        u8::from(*self).format(fmt)
+        defmt::write!(fmt, "{=str}({=u8:#04x})", self.name(), u8::from(*self))
     }
 }
 
@@ -118,7 +177,6 @@ macro_rules! impl_bitflags_reg8 {
 /// Who I Am 1 (Company ID)
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub struct Wia1(
     /// Company ID
     pub u8,
@@ -131,10 +189,29 @@ impl Wia1 {
 
 impl_transparent_reg8!(Wia1, RegisterAddress::Wia1);
 
+/// Formats the known value as "AKM", and other values as e.g. "Unknown(0x00)"
+impl fmt::Display for Wia1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Wia1::AKM => f.write_str("AKM"),
+            Wia1(value) => write!(f, "Unknown({:#04x})", value),
+        }
+    }
+}
+
+#[cfg(feature = "defmt-1")]
+impl defmt::Format for Wia1 {
+    fn format(&self, fmt: defmt::Formatter) {
+        match *self {
+            Wia1::AKM => defmt::write!(fmt, "AKM"),
+            Wia1(value) => defmt::write!(fmt, "Unknown({=u8:#04x})", value),
+        }
+    }
+}
+
 /// Who I Am 2 (Device ID)
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub struct Wia2(
     /// Device ID
     pub u8,
@@ -147,10 +224,32 @@ impl Wia2 {
 
 impl_transparent_reg8!(Wia2, RegisterAddress::Wia2);
 
+/// Formats the known value as "AK09916", and other values as e.g. "Unknown(0x00)"
+impl fmt::Display for Wia2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Wia2::AK09916 => f.write_str("AK09916"),
+            Wia2(value) => write!(f, "Unknown({:#04x})", value),
+        }
+    }
+}
+
+#[cfg(feature = "defmt-1")]
+impl defmt::Format for Wia2 {
+    fn format(&self, fmt: defmt::Formatter) {
+        match *self {
+            Wia2::AK09916 => defmt::write!(fmt, "AK09916"),
+            Wia2(value) => defmt::write!(fmt, "Unknown({=u8:#04x})", value),
+        }
+    }
+}
+
 bitflags_macro! {
     /// Status 1
     #[repr(transparent)]
     pub struct St1: u8 {
+        /// Reserved bits, which read as zero
+        const RESERVED = 0b1111_1100;
         /// Data Overrun
         const DOR = 1 << 1;
         /// Data Ready
@@ -160,6 +259,17 @@ bitflags_macro! {
 
 impl_bitflags_reg8!(St1, RegisterAddress::St1);
 
+impl St1 {
+    /// Returns true if measurement data is ready
+    pub fn data_ready(self) -> bool {
+        self.contains(St1::DRDY)
+    }
+    /// Returns true if data overrun has happened
+    pub fn data_overrun(self) -> bool {
+        self.contains(St1::DOR)
+    }
+}
+
 /// Measurement Magnetic Data (X axis, LSB)
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -301,6 +411,33 @@ impl From<Hz> for i16 {
     }
 }
 
+#[inline]
+pub(crate) fn axes_from_raw_data(buffer: [u8; 6]) -> (Hx, Hy, Hz) {
+    (
+        Hx(i16::from_le_bytes([buffer[0], buffer[1]])),
+        Hy(i16::from_le_bytes([buffer[2], buffer[3]])),
+        Hz(i16::from_le_bytes([buffer[4], buffer[5]])),
+    )
+}
+
+impl From<Measurement> for (Hx, Hy, Hz) {
+    fn from(measurement: Measurement) -> Self {
+        (Hx(measurement.hx), Hy(measurement.hy), Hz(measurement.hz))
+    }
+}
+
+/// Creates a measurement with empty flags
+impl From<(Hx, Hy, Hz)> for Measurement {
+    fn from((hx, hy, hz): (Hx, Hy, Hz)) -> Self {
+        Measurement {
+            hx: hx.0,
+            hy: hy.0,
+            hz: hz.0,
+            flags: MeasurementFlags::empty(),
+        }
+    }
+}
+
 bitflags_macro! {
     /// Status 2
     #[repr(transparent)]
@@ -316,6 +453,13 @@ bitflags_macro! {
     }
 }
 
+impl St2 {
+    /// Returns true if magnetic sensor overflow has happened
+    pub fn sensor_overflow(self) -> bool {
+        self.contains(St2::HOFL)
+    }
+}
+
 impl Register8 for St2 {
     const ADDRESS: RegisterAddress = RegisterAddress::St2;
 }
@@ -340,11 +484,32 @@ pub enum ModeRegister {
     Other(u8),
 }
 
+impl fmt::Display for ModeRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModeRegister::Mode(mode) => write!(f, "{:?}", mode),
+            ModeRegister::Other(value) => write!(f, "Unknown({:#04x})", value),
+        }
+    }
+}
+
+/// Known modes are ordered by their register value, and other values are only comparable for
+/// equality
+impl PartialOrd for ModeRegister {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (ModeRegister::Mode(a), ModeRegister::Mode(b)) => a.partial_cmp(b),
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
 impl From<u8> for ModeRegister {
     fn from(value: u8) -> Self {
         match Mode::try_from(value & 0b11111) {
             Ok(mode) => ModeRegister::Mode(mode),
-            Err(err) => ModeRegister::Other(err.number),
+            Err(err) => ModeRegister::Other(err.value),
         }
     }
 }
@@ -367,6 +532,36 @@ pub struct Cntl2(
     pub ModeRegister,
 );
 
+impl Cntl2 {
+    /// Returns the operation mode, or None if the register contains an undefined value
+    pub fn mode(self) -> Option<Mode> {
+        match self.0 {
+            ModeRegister::Mode(mode) => Some(mode),
+            ModeRegister::Other(_) => None,
+        }
+    }
+    /// Returns true if the mode is power-down mode
+    pub fn is_power_down(self) -> bool {
+        self.mode() == Some(Mode::PowerDown)
+    }
+    /// Returns true if the mode is one of the continuous measurement modes
+    pub fn is_continuous(self) -> bool {
+        matches!(
+            self.mode(),
+            Some(
+                Mode::Continuous10Hz
+                    | Mode::Continuous20Hz
+                    | Mode::Continuous50Hz
+                    | Mode::Continuous100Hz
+            )
+        )
+    }
+    /// Returns true if the mode is single measurement mode
+    pub fn is_single_shot(self) -> bool {
+        self.mode() == Some(Mode::SingleMeasurement)
+    }
+}
+
 impl From<Mode> for Cntl2 {
     fn from(value: Mode) -> Self {
         Cntl2(ModeRegister::Mode(value))
@@ -393,6 +588,8 @@ bitflags_macro! {
     /// Control 3
     #[repr(transparent)]
     pub struct Cntl3: u8 {
+        /// Reserved bits, which read as zero
+        const RESERVED = 0b1111_1110;
         /// Soft reset
         const SRST = 1 << 0;
     }
@@ -415,7 +612,7 @@ impl From<Cntl3> for u8 {
 }
 
 /// Full dump of non-reserved registers and their bits
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
 pub struct RegisterDump {
     pub company_id: Wia1,
@@ -429,19 +626,198 @@ pub struct RegisterDump {
     pub cntl3: Cntl3,
 }
 
+/// Returns the index of a register in the raw data read by `RegisterDump::from_raw_data`.
+///
+/// The dump is read starting from WIA1, and when auto-incrementing the address, the device skips
+/// from RSV2 to ST1 and from ST2 to CNTL1.
+const fn dump_index(address: RegisterAddress) -> usize {
+    let st1_index = RegisterAddress::Rsv2 as usize - RegisterAddress::Wia1 as usize + 1;
+    let cntl1_index = st1_index + RegisterAddress::St2 as usize - RegisterAddress::St1 as usize + 1;
+    let address = address as usize;
+    if address >= RegisterAddress::Cntl1 as usize {
+        cntl1_index + address - RegisterAddress::Cntl1 as usize
+    } else if address >= RegisterAddress::St1 as usize {
+        st1_index + address - RegisterAddress::St1 as usize
+    } else {
+        address - RegisterAddress::Wia1 as usize
+    }
+}
+
+const DUMP_WIA1: usize = dump_index(RegisterAddress::Wia1);
+const DUMP_WIA2: usize = dump_index(RegisterAddress::Wia2);
+const DUMP_ST1: usize = dump_index(RegisterAddress::St1);
+const DUMP_HXL: usize = dump_index(RegisterAddress::Hxl);
+const DUMP_HXH: usize = dump_index(RegisterAddress::Hxh);
+const DUMP_HYL: usize = dump_index(RegisterAddress::Hyl);
+const DUMP_HYH: usize = dump_index(RegisterAddress::Hyh);
+const DUMP_HZL: usize = dump_index(RegisterAddress::Hzl);
+const DUMP_HZH: usize = dump_index(RegisterAddress::Hzh);
+const DUMP_ST2: usize = dump_index(RegisterAddress::St2);
+const DUMP_CNTL2: usize = dump_index(RegisterAddress::Cntl2);
+const DUMP_CNTL3: usize = dump_index(RegisterAddress::Cntl3);
+/// Length of the raw data, ending at the last register in the dump
+const DUMP_LEN: usize = DUMP_CNTL3 + 1;
+
+// Verifies the dump layout against the datasheet
+const _: () = {
+    assert!(DUMP_WIA1 == 0);
+    assert!(DUMP_WIA2 == 1);
+    assert!(DUMP_ST1 == 4);
+    assert!(DUMP_HXL == 5);
+    assert!(DUMP_HXH == 6);
+    assert!(DUMP_HYL == 7);
+    assert!(DUMP_HYH == 8);
+    assert!(DUMP_HZL == 9);
+    assert!(DUMP_HZH == 10);
+    assert!(DUMP_ST2 == 12);
+    assert!(DUMP_CNTL2 == 14);
+    assert!(DUMP_CNTL3 == 15);
+    assert!(DUMP_LEN == 16);
+};
+
 impl RegisterDump {
     #[inline]
-    pub(crate) fn from_raw_data(buffer: [u8; 16]) -> Self {
+    pub(crate) fn from_raw_data(buffer: [u8; DUMP_LEN]) -> Self {
         RegisterDump {
-            company_id: Wia1::from(buffer[0]),
-            device_id: Wia2::from(buffer[1]),
-            st1: St1::from(buffer[4]),
-            hx: i16::from_le_bytes([buffer[5], buffer[6]]),
-            hy: i16::from_le_bytes([buffer[7], buffer[8]]),
-            hz: i16::from_le_bytes([buffer[9], buffer[10]]),
-            st2: St2::from(buffer[12]),
-            mode: Cntl2::from(buffer[14]).0,
-            cntl3: Cntl3::from(buffer[15]),
+            company_id: Wia1::from(buffer[DUMP_WIA1]),
+            device_id: Wia2::from(buffer[DUMP_WIA2]),
+            st1: St1::from(buffer[DUMP_ST1]),
+            hx: i16::from_le_bytes([buffer[DUMP_HXL], buffer[DUMP_HXH]]),
+            hy: i16::from_le_bytes([buffer[DUMP_HYL], buffer[DUMP_HYH]]),
+            hz: i16::from_le_bytes([buffer[DUMP_HZL], buffer[DUMP_HZH]]),
+            st2: St2::from(buffer[DUMP_ST2]),
+            mode: Cntl2::from(buffer[DUMP_CNTL2]).0,
+            cntl3: Cntl3::from(buffer[DUMP_CNTL3]),
         }
     }
+    /// Returns the measurement data in the dump, with flags interpreted from `ST1` and `ST2`
+    pub fn to_measurement(&self) -> Measurement {
+        Measurement::from_register_dump(self)
+    }
+    /// Compares this dump to another one, returning the fields that differ.
+    ///
+    /// Fields that changed contain the value from `other`.
+    pub fn diff(&self, other: &RegisterDump) -> RegisterDiff {
+        fn changed<T: PartialEq + Copy>(old: T, new: T) -> Option<T> {
+            if old != new {
+                Some(new)
+            } else {
+                None
+            }
+        }
+        RegisterDiff {
+            company_id: changed(self.company_id, other.company_id),
+            device_id: changed(self.device_id, other.device_id),
+            st1: changed(self.st1, other.st1),
+            hx: changed(self.hx, other.hx),
+            hy: changed(self.hy, other.hy),
+            hz: changed(self.hz, other.hz),
+            st2: changed(self.st2, other.st2),
+            mode: changed(self.mode, other.mode),
+            cntl3: changed(self.cntl3, other.cntl3),
+        }
+    }
+    /// Checks that the dump looks like it came from a working AK09916.
+    ///
+    /// The device IDs must match AK09916, the mode register must contain a valid mode, and the
+    /// reserved bits of `ST1`, `ST2`, and `CNTL3` must be zero.
+    pub fn validate(&self) -> Result<(), RegisterDumpError> {
+        if self.company_id != Wia1::AKM {
+            return Err(RegisterDumpError::UnexpectedCompanyId(self.company_id.0));
+        }
+        if self.device_id != Wia2::AK09916 {
+            return Err(RegisterDumpError::UnexpectedDeviceId(self.device_id.0));
+        }
+        if let ModeRegister::Other(value) = self.mode {
+            return Err(RegisterDumpError::InvalidMode(value));
+        }
+        let reserved = [
+            (RegisterAddress::St1, (self.st1 & St1::RESERVED).bits()),
+            (
+                RegisterAddress::St2,
+                (self.st2 & (St2::RSV28 | St2::RSV29 | St2::RSV30)).bits(),
+            ),
+            (
+                RegisterAddress::Cntl3,
+                (self.cntl3 & Cntl3::RESERVED).bits(),
+            ),
+        ];
+        for (register, bits) in reserved {
+            if bits != 0 {
+                return Err(RegisterDumpError::ReservedBitsSet { register, bits });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats the dump with register names and addresses, e.g.
+/// `RegisterDump { Wia1(0x00)=0x48, Wia2(0x01)=0x09, St1(0x10)=0b00000001, ... }`
+///
+/// The axes are labeled with the address of their low byte register, e.g. `Hxl(0x11)`.
+impl fmt::Debug for RegisterDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RegisterDump {{ {}={:#04x}, {}={:#04x}, {}={:#010b}, {}={}, {}={}, {}={}, \
+             {}={:#010b}, {}={:#04x}, {}={:#010b} }}",
+            RegisterAddress::Wia1,
+            self.company_id.0,
+            RegisterAddress::Wia2,
+            self.device_id.0,
+            RegisterAddress::St1,
+            self.st1.bits(),
+            RegisterAddress::Hxl,
+            self.hx,
+            RegisterAddress::Hyl,
+            self.hy,
+            RegisterAddress::Hzl,
+            self.hz,
+            RegisterAddress::St2,
+            self.st2.bits(),
+            RegisterAddress::Cntl2,
+            u8::from(self.mode),
+            RegisterAddress::Cntl3,
+            self.cntl3.bits(),
+        )
+    }
+}
+
+/// Register dump validation error
+///
+/// New variants may be added in minor releases.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum RegisterDumpError {
+    /// Company ID is not AKM
+    UnexpectedCompanyId(u8),
+    /// Device ID is not AK09916
+    UnexpectedDeviceId(u8),
+    /// Mode register doesn't contain a valid mode
+    InvalidMode(u8),
+    /// Reserved bits of a status or control register are set
+    ReservedBitsSet {
+        /// Register with the reserved bits
+        register: RegisterAddress,
+        /// Reserved bits that are set
+        bits: u8,
+    },
+}
+
+/// Differences between two register dumps
+///
+/// Each field is `Some(new_value)` if the field changed, or `None` if it's unchanged.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-1", derive(defmt::Format))]
+pub struct RegisterDiff {
+    pub company_id: Option<Wia1>,
+    pub device_id: Option<Wia2>,
+    pub st1: Option<St1>,
+    pub hx: Option<i16>,
+    pub hy: Option<i16>,
+    pub hz: Option<i16>,
+    pub st2: Option<St2>,
+    pub mode: Option<ModeRegister>,
+    pub cntl3: Option<Cntl3>,
 }